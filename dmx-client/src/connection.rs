@@ -0,0 +1,234 @@
+use eframe::{
+    egui::{self, vec2, Color32, Sense, TextEdit, Ui},
+    Storage,
+};
+use ewebsock::{WsEvent, WsMessage, WsReceiver, WsSender};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Key of the server url in eframe storage.
+pub const URL_KEY: &str = "server_url";
+
+/// Key of the access token in eframe storage.
+pub const TOKEN_KEY: &str = "server_token";
+
+/// The web client is served by dmx-server, so it connects back to where it was loaded from.
+fn default_url() -> String {
+    #[cfg(target_arch = "wasm32")]
+    if let Some(url) = crate::web::origin_websocket_url() {
+        return url;
+    }
+
+    "ws://127.0.0.1:33333".to_owned()
+}
+
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    /// The socket dropped or failed to open, waiting to reconnect.
+    Failed(String),
+}
+
+/// WebSocket connection to the server that reconnects with backoff when it drops.
+pub struct Connection {
+    pub url: String,
+    /// Operator or viewer token, sent as `?token=` since browsers can't set headers.
+    pub token: String,
+    socket: Option<(WsSender, WsReceiver)>,
+    state: ConnectionState,
+    /// Set by connect and cleared by disconnect, the connection is retried while set.
+    enabled: bool,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+    /// Repainted when a socket event arrives.
+    ctx: egui::Context,
+}
+
+impl Connection {
+    /// Connects to the url saved in `storage`, or the default one.
+    pub fn new(ctx: egui::Context, storage: Option<&dyn Storage>) -> Self {
+        let url = storage
+            .and_then(|storage| storage.get_string(URL_KEY))
+            .unwrap_or_else(default_url);
+        let token = storage
+            .and_then(|storage| storage.get_string(TOKEN_KEY))
+            .unwrap_or_default();
+
+        let mut connection = Self {
+            url,
+            token,
+            socket: None,
+            state: ConnectionState::Disconnected,
+            enabled: false,
+            backoff: MIN_BACKOFF,
+            retry_at: None,
+            ctx,
+        };
+        connection.connect();
+        connection
+    }
+
+    pub fn connect(&mut self) {
+        self.enabled = true;
+        self.backoff = MIN_BACKOFF;
+        self.retry_at = None;
+        self.open();
+    }
+
+    /// Opens a new socket unless disconnected, the server greets every new connection.
+    pub fn reconnect(&mut self) {
+        if self.enabled {
+            self.connect();
+        }
+    }
+
+    pub fn disconnect(&mut self) {
+        self.enabled = false;
+        self.socket = None;
+        self.state = ConnectionState::Disconnected;
+        self.retry_at = None;
+    }
+
+    /// Handles socket events and reconnects when it is time, call once per frame.
+    /// Returns the text messages received from the server.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut messages = Vec::new();
+
+        while let Some(event) = self
+            .socket
+            .as_ref()
+            .and_then(|(_, receiver)| receiver.try_recv())
+        {
+            match event {
+                WsEvent::Opened => {
+                    self.state = ConnectionState::Connected;
+                    self.backoff = MIN_BACKOFF;
+                }
+                WsEvent::Message(WsMessage::Text(text)) => messages.push(text),
+                WsEvent::Message(_) => (),
+                WsEvent::Error(error) => self.dropped(error),
+                WsEvent::Closed => self.dropped("connection closed".to_owned()),
+            }
+        }
+
+        if self.enabled
+            && self.socket.is_none()
+            && self.retry_at.is_some_and(|at| Instant::now() >= at)
+        {
+            self.retry_at = None;
+            self.open();
+        }
+
+        messages
+    }
+
+    /// Time left until the next reconnect attempt.
+    pub fn retry_in(&self) -> Option<Duration> {
+        self.retry_at
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self.state, ConnectionState::Connected)
+    }
+
+    /// Sends `message` if connected, returns false if it was dropped.
+    pub fn send(&mut self, message: WsMessage) -> bool {
+        if let (ConnectionState::Connected, Some((sender, _))) = (&self.state, &mut self.socket) {
+            sender.send(message);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) {
+        storage.set_string(URL_KEY, self.url.clone());
+        storage.set_string(TOKEN_KEY, self.token.clone());
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Server:");
+            ui.text_edit_singleline(&mut self.url);
+            ui.label("Token:");
+            TextEdit::singleline(&mut self.token)
+                .password(true)
+                .desired_width(80.0)
+                .show(ui);
+
+            if self.enabled {
+                if ui.button("Disconnect").clicked() {
+                    self.disconnect();
+                }
+            } else if ui.button("Connect").clicked() {
+                self.connect();
+            }
+
+            let (color, text) = match &self.state {
+                ConnectionState::Disconnected => (Color32::GRAY, "Disconnected".to_owned()),
+                ConnectionState::Connecting => (Color32::YELLOW, "Connecting".to_owned()),
+                ConnectionState::Connected => (Color32::GREEN, "Connected".to_owned()),
+                ConnectionState::Failed(error) => {
+                    let retry = self.retry_in().unwrap_or_default().as_secs_f32();
+                    (
+                        Color32::RED,
+                        format!("{error}, reconnecting in {:.0} s", retry.ceil()),
+                    )
+                }
+            };
+
+            let (rect, _) = ui.allocate_exact_size(vec2(12.0, 12.0), Sense::hover());
+            ui.painter().circle_filled(rect.center(), 5.0, color);
+            ui.label(text);
+        });
+    }
+
+    fn open(&mut self) {
+        let ctx = self.ctx.clone();
+
+        match ewebsock::connect_with_wakeup(self.endpoint(), move || ctx.request_repaint()) {
+            Ok(socket) => {
+                self.socket = Some(socket);
+                self.state = ConnectionState::Connecting;
+            }
+            Err(error) => self.dropped(error),
+        }
+    }
+
+    /// The url with the token added to the query.
+    fn endpoint(&self) -> String {
+        if self.token.is_empty() {
+            return self.url.clone();
+        }
+
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        let token: String = self
+            .token
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (byte as char).to_string()
+                }
+                _ => format!("%{byte:02X}"),
+            })
+            .collect();
+
+        format!("{}{separator}token={token}", self.url)
+    }
+
+    fn dropped(&mut self, error: String) {
+        self.socket = None;
+        self.state = ConnectionState::Failed(error);
+        self.retry_at = Some(Instant::now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,229 @@
+use dmx_shared::show::Show;
+use serde::Serialize;
+
+const MAX_HISTORY: usize = 100;
+
+/// State kept in a `History`.
+pub trait Snapshot: Clone + Serialize {
+    /// Keeps the runtime state of `previous` when a snapshot is restored over it.
+    fn carry_over(&mut self, previous: &Self);
+}
+
+impl Snapshot for Show {
+    fn carry_over(&mut self, previous: &Self) {
+        Show::carry_over(self, previous);
+    }
+}
+
+/// Undo/redo stack of snapshots.
+///
+/// Changes are detected by comparing the serialized form of the state with
+/// the last committed snapshot, so every persisted edit is covered without
+/// each widget having to report what it changed.
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    committed: T,
+    committed_json: String,
+}
+
+impl<T: Snapshot> History<T> {
+    pub fn new(state: &T) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            committed: state.clone(),
+            committed_json: serde_json::to_string(state).unwrap_or_default(),
+        }
+    }
+
+    /// Commits `state` as a new undo step if it differs from the last one.
+    pub fn record(&mut self, state: &T) {
+        let json = serde_json::to_string(state).unwrap_or_default();
+
+        if json == self.committed_json {
+            return;
+        }
+
+        self.undo
+            .push(std::mem::replace(&mut self.committed, state.clone()));
+        self.committed_json = json;
+        self.redo.clear();
+
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn undo(&mut self, state: &mut T) {
+        self.record(state);
+
+        if let Some(previous) = self.undo.pop() {
+            self.redo
+                .push(std::mem::replace(&mut self.committed, previous));
+            self.restore(state);
+        }
+    }
+
+    pub fn redo(&mut self, state: &mut T) {
+        self.record(state);
+
+        if let Some(next) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.committed, next));
+            self.restore(state);
+        }
+    }
+
+    /// Applies an edit made elsewhere to the committed state as well, so it isn't taken
+    /// for a local edit. `edit` returns whether it changed anything.
+    pub fn rebase(&mut self, edit: impl FnOnce(&mut T) -> bool) -> bool {
+        if !edit(&mut self.committed) {
+            return false;
+        }
+
+        self.committed_json = serde_json::to_string(&self.committed).unwrap_or_default();
+        true
+    }
+
+    /// Serialized form of the last committed state.
+    pub fn committed_json(&self) -> &str {
+        &self.committed_json
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Undo only reverts edits, playback and trigger timing carry on as they were.
+    fn restore(&mut self, state: &mut T) {
+        let previous = std::mem::replace(state, self.committed.clone());
+        state.carry_over(&previous);
+        self.committed_json = serde_json::to_string(state).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dmx_shared::{dmx_device::Position, show};
+
+    const SHOW: &str = r#"{
+        "version": 1,
+        "lights": [0, 1, 2, 3, 4],
+        "devices": [
+            {"enabled": true, "adress": 0, "size": 4, "name": "Par", "cycle_length": 2.0, "timelines": []}
+        ]
+    }"#;
+
+    fn show() -> Show {
+        show::from_json(SHOW).unwrap()
+    }
+
+    #[test]
+    fn records_only_changes() {
+        let mut state = show();
+        let mut history = History::new(&state);
+
+        history.record(&state);
+        assert!(!history.can_undo());
+
+        state.bpm = 90.0;
+        history.record(&state);
+        history.record(&state);
+        assert!(history.can_undo());
+
+        history.undo(&mut state);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut state = show();
+        let mut history = History::new(&state);
+        let original = state.bpm;
+
+        state.bpm = 90.0;
+        history.record(&state);
+
+        history.undo(&mut state);
+        assert_eq!(state.bpm, original);
+        assert_eq!(
+            history.committed_json(),
+            serde_json::to_string(&state).unwrap()
+        );
+
+        history.redo(&mut state);
+        assert_eq!(state.bpm, 90.0);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undo_commits_pending_edits_first() {
+        let mut state = show();
+        let mut history = History::new(&state);
+
+        state.bpm = 90.0;
+        history.undo(&mut state);
+        assert!(history.can_redo());
+
+        history.redo(&mut state);
+        assert_eq!(state.bpm, 90.0);
+    }
+
+    #[test]
+    fn edits_clear_redo() {
+        let mut state = show();
+        let mut history = History::new(&state);
+
+        state.bpm = 90.0;
+        history.record(&state);
+        history.undo(&mut state);
+
+        state.bpm = 100.0;
+        history.record(&state);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn keeps_at_most_max_history_steps() {
+        let mut state = show();
+        let mut history = History::new(&state);
+
+        for bpm in 0..MAX_HISTORY + 10 {
+            state.bpm = bpm as f32;
+            history.record(&state);
+        }
+
+        let mut steps = 0;
+        while history.can_undo() {
+            history.undo(&mut state);
+            steps += 1;
+        }
+        assert_eq!(steps, MAX_HISTORY);
+    }
+
+    #[test]
+    fn restoring_keeps_runtime_state() {
+        let mut state = show();
+        let mut history = History::new(&state);
+
+        state.bpm = 90.0;
+        history.record(&state);
+
+        let position = Position {
+            phase: 0.25,
+            seek_offset: 0.0,
+        };
+        state.devices[0].set_position(position);
+
+        history.undo(&mut state);
+        assert_eq!(state.devices[0].position().phase, 0.25);
+
+        history.redo(&mut state);
+        assert_eq!(state.devices[0].position().phase, 0.25);
+    }
+}
@@ -1,21 +1,38 @@
-use channel::ChannelWidget;
-use dmx_device::DmxDevice;
-use dmx_shared::{DmxColor, DmxMessage};
+use connection::Connection;
+use dmx_shared::{
+    cue::CuePlayer,
+    dmx_device::DmxDevice,
+    group::FixtureGroup,
+    playback::{self, Command, Playback, PlaybackState},
+    show::{self, Show},
+    ServerMessage,
+};
 use eframe::{
-    egui::{self, DragValue, Slider, Widget},
+    egui::{self, Button, Key, KeyboardShortcut, Modifiers},
     Storage,
 };
-use ewebsock::{WsMessage, WsReceiver, WsSender};
-use serde::{Deserialize, Serialize};
-use std::fs;
+use history::History;
+use output::Output;
+use transport::Transport;
+
+#[cfg(not(target_arch = "wasm32"))]
+use show::ShowError;
 
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 #[cfg(target_arch = "wasm32")]
 use web_time::Instant;
 
-mod channel;
-mod dmx_device;
+mod connection;
+mod history;
+mod output;
+mod transport;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+/// Key of the show in eframe storage, used by the web build.
+#[cfg(target_arch = "wasm32")]
+const SHOW_KEY: &str = "show";
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
@@ -24,18 +41,23 @@ fn main() -> eframe::Result<()> {
         ..Default::default()
     };
 
-    let state = fs::read_to_string("state.json")
-        .ok()
-        .and_then(|s| serde_json::from_str::<State>(&s).ok())
-        .unwrap_or(State {
-            lights: [0, 1, 2, 3, 4],
-            devices: Vec::new(),
-        });
+    let show = show::load(show::DEFAULT_PATH);
 
     eframe::run_native(
         "Voysys DMX controller",
         options,
-        Box::new(|_cc| Box::new(App::new(state))),
+        Box::new(|cc| {
+            let app = match show {
+                Ok(show) => App::new(show, cc),
+                Err(ShowError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                    App::new(Show::default(), cc)
+                }
+                Err(e) => App::new(Show::default(), cc)
+                    .load_failed(format!("Failed to open {}: {e}", show::DEFAULT_PATH)),
+            };
+
+            Box::new(app)
+        }),
     )
 }
 
@@ -47,64 +69,278 @@ fn main() {
 
     let web_options = eframe::WebOptions::default();
 
-    let state = State {
-        cycle_length: 5.0,
-        timelines: vec![
-            Timeline::new(0),
-            Timeline::new(1),
-            Timeline::new(2),
-            Timeline::new(3),
-            Timeline::new(4),
-        ],
-        lights: [0, 1, 2, 3, 4],
-    };
-
     wasm_bindgen_futures::spawn_local(async {
         eframe::WebRunner::new()
             .start(
                 "voysys-dmx", // hardcode it
                 web_options,
-                Box::new(|cc| Box::new(App::new(state))),
+                Box::new(|cc| {
+                    let stored = cc.storage.and_then(|storage| storage.get_string(SHOW_KEY));
+
+                    let app = match stored.map(|s| show::from_json(&s)) {
+                        Some(Ok(show)) => App::new(show, cc),
+                        None => App::new(Show::default(), cc),
+                        Some(Err(e)) => App::new(Show::default(), cc).load_failed(format!(
+                            "Failed to restore the show from browser storage: {e}"
+                        )),
+                    };
+
+                    Box::new(app)
+                }),
             )
             .await
             .expect("failed to start eframe");
     });
 }
 
-#[derive(Serialize, Deserialize)]
-struct State {
-    lights: [i32; 5],
-    devices: Vec<DmxDevice>,
-}
-
 struct App {
-    ws_sender: WsSender,
-    ws_receiver: WsReceiver,
+    connection: Connection,
+    output: Output,
 
     last_frame_time: Instant,
-
-    state: State,
-    smoke: Option<u8>,
+    playback: Playback,
+    transport: Transport,
+
+    state: Show,
+    history: History<Show>,
+
+    /// File the show was opened from, autosaved on exit.
+    #[cfg(not(target_arch = "wasm32"))]
+    show_path: String,
+    /// Path typed into the show file field.
+    path_edit: String,
+    show_status: Option<Result<String, String>>,
+    /// Cleared when the show file failed to load or the show of the server is followed,
+    /// so the file isn't overwritten.
+    autosave: bool,
+
+    /// Show received from the server, applied once no edit is in progress.
+    incoming: Option<Show>,
+    /// Playback state received with `incoming`, applied after it.
+    incoming_playback: Option<PlaybackState>,
 }
 
 impl App {
-    fn new(state: State) -> Self {
-        let (ws_sender, ws_receiver) = ewebsock::connect("ws://10.0.11.3:33333").unwrap();
-
+    fn new(state: Show, cc: &eframe::CreationContext) -> Self {
         Self {
-            ws_sender,
-            ws_receiver,
+            connection: Connection::new(cc.egui_ctx.clone(), cc.storage),
+            output: Output::new(cc.storage),
             last_frame_time: Instant::now(),
+            playback: Playback::default(),
+            transport: Transport::default(),
+            history: History::new(&state),
             state,
-            smoke: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            show_path: show::DEFAULT_PATH.to_owned(),
+            path_edit: show::DEFAULT_PATH.to_owned(),
+            show_status: None,
+            autosave: true,
+            incoming: None,
+            incoming_playback: None,
+        }
+    }
+
+    /// Keeps the stored show around instead of overwriting it with an empty one.
+    fn load_failed(mut self, error: String) -> Self {
+        self.autosave = false;
+        self.show_status = Some(Err(error));
+        self
+    }
+
+    fn set_show(&mut self, state: Show) {
+        self.history = History::new(&state);
+        self.state = state;
+        self.playback.cue_player = CuePlayer::default();
+        self.autosave = true;
+    }
+
+    /// Applies `command` to the local playback and sends it to the server if it plays the show.
+    fn command(&mut self, command: Command) {
+        self.output.command(&mut self.connection, command.clone());
+        self.playback.apply(&mut self.state, command);
+    }
+
+    /// Follows the show and the commands of other clients when the server plays the show.
+    fn receive(&mut self, message: &str) {
+        if !self.output.is_remote() {
+            return;
+        }
+
+        match serde_json::from_str(message) {
+            Ok(ServerMessage::Show(Some(show))) => self.incoming = Some(show),
+            Ok(ServerMessage::Show(None)) => self.output.synced(None),
+            Ok(ServerMessage::Command(command)) => {
+                // Master levels are part of the show, but the server already has them.
+                let previous = self.history.committed_json().to_owned();
+                if self.history.rebase(|show| command.edit(show)) {
+                    self.output
+                        .rebased(&previous, self.history.committed_json());
+                }
+
+                self.playback.apply(&mut self.state, command);
+            }
+            Ok(ServerMessage::Playback(state)) => {
+                if self.incoming.is_some() {
+                    self.incoming_playback = Some(state);
+                } else {
+                    self.playback.restore(&mut self.state, state);
+                }
+            }
+            Err(_) => (),
+        }
+    }
+
+    /// Replaces the show with the one received from the server. Call when no edit is in
+    /// progress and after recording, a local edit would be lost otherwise.
+    fn follow(&mut self) {
+        let Some(mut show) = self.incoming.take() else {
+            return;
+        };
+
+        // A local edit made while this show was on its way is uploaded after it,
+        // so it wins on the server as well.
+        if !self.output.is_pending(self.history.committed_json()) {
+            show.rehydrate();
+            show.carry_over(&self.state);
+
+            // Their edit isn't ours to undo.
+            self.history.rebase(|committed| {
+                *committed = show.clone();
+                true
+            });
+            self.state = show;
+
+            // The server's show isn't ours to autosave over the file it was opened from.
+            if self.autosave {
+                self.autosave = false;
+                self.show_status = Some(Ok(
+                    "Following the server's show, save it to keep a local copy".to_owned(),
+                ));
+            }
+
+            self.output
+                .synced(Some(self.history.committed_json().to_owned()));
+        }
+
+        if let Some(state) = self.incoming_playback.take() {
+            self.playback.restore(&mut self.state, state);
+        }
+    }
+
+    /// Imports a show file dropped on the window.
+    fn import(&mut self, file: egui::DroppedFile) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &file.path {
+            self.open(path.display().to_string());
+            return;
+        }
+
+        let Some(bytes) = &file.bytes else {
+            return;
+        };
+
+        match show::from_json(&String::from_utf8_lossy(bytes)) {
+            Ok(state) => {
+                self.set_show(state);
+                self.show_status = Some(Ok(format!("Imported {}", file.name)));
+                self.path_edit = file.name;
+            }
+            Err(e) => self.show_status = Some(Err(format!("Failed to import {}: {e}", file.name))),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open(&mut self, path: String) {
+        match show::load(&path) {
+            Ok(state) => {
+                self.set_show(state);
+                self.show_status = Some(Ok(format!("Opened {path}")));
+                self.path_edit = path.clone();
+                self.show_path = path;
+            }
+            Err(e) => self.show_status = Some(Err(format!("Failed to open {path}: {e}"))),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_as(&mut self, path: String) {
+        match show::save(&path, &self.state) {
+            Ok(()) => {
+                self.show_status = Some(Ok(format!("Saved {path}")));
+                self.show_path = path;
+                self.autosave = true;
+            }
+            Err(e) => self.show_status = Some(Err(format!("Failed to save {path}: {e}"))),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export(&mut self, name: String) {
+        let result = show::to_json(&self.state)
+            .map_err(|e| e.to_string())
+            .and_then(|json| web::download(&name, &json).map_err(|e| format!("{e:?}")));
+
+        self.show_status = Some(match result {
+            Ok(()) => Ok(format!("Exported {name}")),
+            Err(e) => Err(format!("Failed to export {name}: {e}")),
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn show_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Show:");
+            ui.text_edit_singleline(&mut self.path_edit);
+
+            if ui.button("Export").clicked() {
+                self.export(self.path_edit.clone());
+            }
+
+            ui.label("Drop a show file on the page to import it");
+        });
+
+        self.status_ui(ui);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Show:");
+            ui.text_edit_singleline(&mut self.path_edit);
+
+            if ui.button("Open").clicked() {
+                self.open(self.path_edit.clone());
+            }
+
+            if ui.button("Save").clicked() {
+                self.save_as(self.show_path.clone());
+            }
+
+            if ui.button("Save As").clicked() {
+                self.save_as(self.path_edit.clone());
+            }
+
+            ui.label(format!("Current: {}", self.show_path));
+        });
+
+        self.status_ui(ui);
+    }
+
+    fn status_ui(&self, ui: &mut egui::Ui) {
+        match &self.show_status {
+            Some(Ok(status)) => {
+                ui.label(status);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            None => (),
         }
     }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.request_repaint();
-
         let dt = {
             let new_time = Instant::now();
             let dt = new_time
@@ -116,8 +352,62 @@ impl eframe::App for App {
 
         // self.state.timelines.retain(|timeline| timeline.id > -1);
 
+        let (undo, redo) = ctx.input_mut(|i| {
+            (
+                i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Z)),
+                i.consume_shortcut(&KeyboardShortcut::new(
+                    Modifiers::COMMAND | Modifiers::SHIFT,
+                    Key::Z,
+                )),
+            )
+        });
+
+        for file in ctx.input_mut(|i| std::mem::take(&mut i.raw.dropped_files)) {
+            self.import(file);
+        }
+
+        if undo {
+            self.history.undo(&mut self.state);
+        }
+        if redo {
+            self.history.redo(&mut self.state);
+        }
+
+        for message in self.connection.poll() {
+            self.receive(&message);
+        }
+
+        // Live controls are collected and applied after the ui, see `App::command`.
+        let mut commands = Vec::new();
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
+                self.connection.ui(ui);
+                ui.separator();
+                if self.output.ui(ui) {
+                    // The server sends its show to new connections.
+                    self.connection.reconnect();
+                }
+            });
+            self.show_ui(ui);
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.history.can_undo(), Button::new("Undo"))
+                    .clicked()
+                {
+                    self.history.undo(&mut self.state);
+                }
+
+                if ui
+                    .add_enabled(self.history.can_redo(), Button::new("Redo"))
+                    .clicked()
+                {
+                    self.history.redo(&mut self.state);
+                }
+
+                ui.separator();
+
                 if ui.button("Add DMX Device").clicked() {
                     self.state.devices.push(DmxDevice::default());
                 }
@@ -135,14 +425,69 @@ impl eframe::App for App {
                 }
             });
 
-            let mut res = DmxMessage {
-                buffer: vec![0u8; 512],
-            };
+            commands.extend(self.playback.clock.ui(ui, &mut self.state.bpm));
+
+            let position = self.state.devices.first().map_or(0.0, |d| d.time());
+
+            commands.extend(self.transport.ui(ui, self.playback.playing, position));
+
+            egui::CollapsingHeader::new("Triggers")
+                .default_open(true)
+                .show(ui, |ui| {
+                    for (index, device) in self.state.devices.iter().enumerate() {
+                        commands.extend(device.triggers_ui(ui, index));
+                    }
+                    for (index, group) in self.state.groups.iter().enumerate() {
+                        commands.extend(group.triggers_ui(ui, index));
+                    }
+                });
 
             for (index, device) in self.state.devices.iter_mut().enumerate() {
-                device.update(ui, index, &mut res, dt);
+                commands.extend(device.ui(ui, index));
+            }
+
+            egui::CollapsingHeader::new("Groups")
+                .default_open(false)
+                .show(ui, |ui| {
+                    if ui.button("Add Group").clicked() {
+                        let name = format!("Group {}", self.state.groups.len());
+                        self.state.groups.push(FixtureGroup::new(name));
+                    }
+
+                    let mut delete = None;
+                    for (index, group) in self.state.groups.iter_mut().enumerate() {
+                        if group.ui(ui, index, &self.state.devices) {
+                            delete = Some(index);
+                        }
+                    }
+
+                    if let Some(index) = delete {
+                        self.state.groups.remove(index);
+                    }
+                });
+
+            let mut selected = vec![false; playback::UNIVERSE];
+            for device in self.state.devices.iter().filter(|device| device.selected) {
+                for channel in device.channels() {
+                    if let Some(selected) = selected.get_mut(channel) {
+                        *selected = true;
+                    }
+                }
             }
 
+            commands.extend(
+                self.playback
+                    .cue_player
+                    .ui(ui, &mut self.state.cues, &selected),
+            );
+
+            commands.extend(self.state.masters.ui(
+                ui,
+                &self.state.devices,
+                self.playback.blackout,
+                &self.playback.flash,
+            ));
+
             /* for i in &mut self.state.lights.iter_mut() {
                 Slider::new(i, 0..=(self.state.timelines.len() as i32 - 1)).ui(ui);
             }
@@ -153,12 +498,6 @@ impl eframe::App for App {
                     .push(Timeline::new(self.state.timelines.len() as i8))
             }
 
-            if ui.button("Add smoke").is_pointer_button_down_on() {
-                self.smoke = Some(128);
-            } else {
-                self.smoke = None;
-            }
-
             for timeline in &mut self.state.timelines.iter_mut() {
                 ui.horizontal(|ui| {
                     ui.label("Gain");
@@ -200,20 +539,55 @@ impl eframe::App for App {
                     res.buffer[start..end].copy_from_slice(&timeline.color.dmx());
                 }
             }
-
-            res.buffer[63] = self.smoke.unwrap_or_default();
             */
+        });
+
+        for command in commands {
+            self.command(command);
+        }
 
-            while let Some(_event) = self.ws_receiver.try_recv() {}
+        let res = self.playback.frame(&mut self.state, dt);
+        let next_send = self.output.send(&mut self.connection, &res);
 
-            self.ws_sender
-                .send(WsMessage::Text(serde_json::to_string(&res).unwrap()));
-        });
+        // Only repaint continuously while something moves, otherwise wait for input,
+        // the next frame or keepalive that is due, or the next reconnect attempt.
+        if self.playback.is_animated(&self.state) {
+            ctx.request_repaint();
+        } else {
+            let retry = self.connection.retry_in().unwrap_or(next_send);
+            ctx.request_repaint_after(next_send.min(retry));
+        }
+
+        // Only commit an undo step once the current drag or text edit is finished.
+        let editing = ctx.input(|i| i.pointer.any_down()) || ctx.memory(|m| m.focus().is_some());
+        if !editing {
+            self.history.record(&self.state);
+            self.follow();
+            self.output.upload(
+                &mut self.connection,
+                &self.state,
+                self.history.committed_json(),
+            );
+        }
     }
 
-    fn save(&mut self, _storage: &mut dyn Storage) {
-        let s = serde_json::to_string(&self.state).unwrap();
-        fs::write("state.json", s).ok();
+    fn save(&mut self, storage: &mut dyn Storage) {
+        self.connection.save(storage);
+        self.output.save(storage);
+
+        if !self.autosave {
+            return;
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        if let Ok(json) = show::to_json(&self.state) {
+            storage.set_string(SHOW_KEY, json);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = show::save(&self.show_path, &self.state) {
+            self.show_status = Some(Err(format!("Failed to save {}: {e}", self.show_path)));
+        }
     }
 }
 
@@ -0,0 +1,208 @@
+use dmx_shared::{playback::Command, show::Show, ClientMessage, DmxMessage};
+use eframe::{
+    egui::{ComboBox, DragValue, Ui, Widget},
+    Storage,
+};
+use ewebsock::WsMessage;
+use std::time::Duration;
+
+use crate::connection::Connection;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Key of the output rate in eframe storage.
+pub const RATE_KEY: &str = "output_rate";
+
+/// Key of the playback mode in eframe storage.
+pub const MODE_KEY: &str = "output_mode";
+
+const DEFAULT_RATE: f32 = 30.0;
+
+/// Unchanged frames are still resent this often so the server knows we're alive.
+const KEEPALIVE: Duration = Duration::from_secs(1);
+
+/// Where the show is played.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// The client renders the show and streams the universe to the server.
+    Local,
+    /// The server plays the show, the client uploads edits and sends commands.
+    Server,
+}
+
+impl Mode {
+    const ALL: [Mode; 2] = [Mode::Local, Mode::Server];
+
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Local => "Local",
+            Mode::Server => "Server",
+        }
+    }
+}
+
+/// Limits how often universe frames are sent to the server and skips unchanged ones.
+pub struct Output {
+    pub mode: Mode,
+    /// Frames per second.
+    pub rate: f32,
+    last_frame: Vec<u8>,
+    last_sent: Option<Instant>,
+    /// Serialized show last uploaded to or received from the server.
+    uploaded: Option<String>,
+    /// Whether the show of the server has been received since connecting.
+    synced: bool,
+}
+
+impl Output {
+    pub fn new(storage: Option<&dyn Storage>) -> Self {
+        let rate = storage
+            .and_then(|storage| storage.get_string(RATE_KEY))
+            .and_then(|rate| rate.parse().ok())
+            .unwrap_or(DEFAULT_RATE);
+        let mode = match storage.and_then(|storage| storage.get_string(MODE_KEY)) {
+            Some(mode) if mode == Mode::Server.name() => Mode::Server,
+            _ => Mode::Local,
+        };
+
+        Self {
+            mode,
+            rate,
+            last_frame: Vec::new(),
+            last_sent: None,
+            uploaded: None,
+            synced: false,
+        }
+    }
+
+    /// Whether the client follows the show played by the server.
+    pub fn is_remote(&self) -> bool {
+        self.mode == Mode::Server
+    }
+
+    /// Sends `message` if it is due, returns how long until the next frame can be sent.
+    /// Nothing is sent while the server plays the show.
+    pub fn send(&mut self, connection: &mut Connection, message: &DmxMessage) -> Duration {
+        if self.mode == Mode::Server {
+            return KEEPALIVE;
+        }
+
+        let now = Instant::now();
+        let interval = Duration::from_secs_f32(1.0 / self.rate.max(1.0));
+
+        if let Some(last_sent) = self.last_sent {
+            let elapsed = now.saturating_duration_since(last_sent);
+
+            if elapsed < interval {
+                return interval - elapsed;
+            }
+
+            if message.buffer == self.last_frame && elapsed < KEEPALIVE {
+                return KEEPALIVE - elapsed;
+            }
+        }
+
+        let Ok(json) = serde_json::to_string(&ClientMessage::Frame(message.clone())) else {
+            return interval;
+        };
+
+        if connection.send(WsMessage::Text(json)) {
+            self.last_frame.clone_from(&message.buffer);
+            self.last_sent = Some(now);
+        } else {
+            // Resend the current frame as soon as the connection is back.
+            self.last_sent = None;
+        }
+
+        interval
+    }
+
+    /// Uploads `show` when the server plays it and it differs from the last upload.
+    /// `json` is its serialized form, used to detect changes. Nothing is uploaded until
+    /// the show of the server has been received, so joining doesn't overwrite it.
+    pub fn upload(&mut self, connection: &mut Connection, show: &Show, json: &str) {
+        if self.mode != Mode::Server || !connection.is_connected() {
+            self.uploaded = None;
+            self.synced = false;
+            return;
+        }
+
+        if !self.synced || self.uploaded.as_deref() == Some(json) {
+            return;
+        }
+
+        let Ok(message) = serde_json::to_string(&ClientMessage::Show(show.clone())) else {
+            return;
+        };
+
+        if connection.send(WsMessage::Text(message)) {
+            self.uploaded = Some(json.to_owned());
+        }
+    }
+
+    /// Whether the show serialized as `json` is still to be uploaded.
+    pub fn is_pending(&self, json: &str) -> bool {
+        self.mode == Mode::Server && self.synced && self.uploaded.as_deref() != Some(json)
+    }
+
+    /// Call when the show of the server is received, `json` is its serialized form.
+    /// `None` if the server has no show yet, ours is uploaded then.
+    pub fn synced(&mut self, json: Option<String>) {
+        self.synced = true;
+        self.uploaded = json;
+    }
+
+    /// Call when the committed show changed from `previous` to `json` by an edit the
+    /// server already has, so it isn't uploaded back.
+    pub fn rebased(&mut self, previous: &str, json: &str) {
+        if self.uploaded.as_deref() == Some(previous) {
+            self.uploaded = Some(json.to_owned());
+        }
+    }
+
+    /// Sends `command` to the server if it plays the show.
+    pub fn command(&mut self, connection: &mut Connection, command: Command) {
+        if self.mode != Mode::Server {
+            return;
+        }
+
+        if let Ok(json) = serde_json::to_string(&ClientMessage::Command(command)) {
+            connection.send(WsMessage::Text(json));
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) {
+        storage.set_string(RATE_KEY, self.rate.to_string());
+        storage.set_string(MODE_KEY, self.mode.name().to_owned());
+    }
+
+    /// Returns true if the mode changed.
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+
+        ui.label("Playback");
+        ComboBox::from_id_source("output_mode")
+            .selected_text(self.mode.name())
+            .show_ui(ui, |ui| {
+                for mode in Mode::ALL {
+                    changed |= ui
+                        .selectable_value(&mut self.mode, mode, mode.name())
+                        .changed();
+                }
+            });
+
+        if self.mode == Mode::Local {
+            ui.label("Output rate");
+            DragValue::new(&mut self.rate)
+                .clamp_range(1.0..=60.0)
+                .speed(0.1)
+                .suffix(" Hz")
+                .ui(ui);
+        }
+
+        changed
+    }
+}
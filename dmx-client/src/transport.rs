@@ -0,0 +1,43 @@
+use dmx_shared::playback::Command;
+use eframe::egui::{Slider, Ui, Widget};
+
+/// Play, pause and stop buttons with a scrub bar.
+#[derive(Default)]
+pub struct Transport {
+    position: f32,
+}
+
+impl Transport {
+    /// `position` is the current position shown on the scrub bar while it is not being dragged.
+    pub fn ui(&mut self, ui: &mut Ui, playing: bool, position: f32) -> Option<Command> {
+        let mut command = None;
+
+        ui.horizontal(|ui| {
+            if ui.button(if playing { "Pause" } else { "Play" }).clicked() {
+                command = Some(if playing {
+                    Command::Pause
+                } else {
+                    Command::Play
+                });
+            }
+
+            if ui.button("Stop").clicked() {
+                command = Some(Command::Stop);
+            }
+
+            ui.label("Position");
+
+            let response = Slider::new(&mut self.position, 0.0..=1000.0)
+                .show_value(false)
+                .ui(ui);
+
+            if response.changed() {
+                command = Some(Command::Seek(self.position));
+            } else if !response.dragged() {
+                self.position = position;
+            }
+        });
+
+        command
+    }
+}
@@ -0,0 +1,176 @@
+use serde::Deserialize;
+use std::{fs, io, net::IpAddr, path::PathBuf};
+
+pub const DEFAULT_PATH: &str = "server.json";
+
+/// Server settings read from `server.json`, every field is optional.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Where the web client build from `trunk build` is served from.
+    pub web_root: PathBuf,
+    /// Token that grants the operator role. Without it every client is an operator.
+    pub operator_token: Option<String>,
+    /// Token that grants the viewer role, viewers need no token when it is unset.
+    pub viewer_token: Option<String>,
+    /// Addresses or networks like `10.0.11.0/24` allowed to connect, empty allows all.
+    pub allowed: Vec<String>,
+    /// PEM certificate chain and private key, serves https and wss when both are set.
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Show file played by the server, shows uploaded by clients are saved back to it.
+    pub show: Option<PathBuf>,
+    /// Records everything sent to the port to this file, overwriting it.
+    pub record: Option<PathBuf>,
+    /// Plays this recording to the port instead of serving clients.
+    pub replay: Option<PathBuf>,
+    /// Starts the replay over when it ends instead of exiting.
+    pub replay_loop: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            web_root: PathBuf::from("dmx-client/dist"),
+            operator_token: None,
+            viewer_token: None,
+            allowed: Vec::new(),
+            tls_cert: None,
+            tls_key: None,
+            show: None,
+            record: None,
+            replay: None,
+            replay_loop: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    /// Can watch but not drive the rig.
+    Viewer,
+    Operator,
+}
+
+impl Config {
+    /// Loads the config from `path`, a missing file gives the defaults.
+    pub fn load(path: &str) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str(&s).map_err(io::Error::from),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether `record` is the file `replay` plays, which recording would truncate.
+    pub fn records_over_replay(&self) -> bool {
+        let (Some(record), Some(replay)) = (&self.record, &self.replay) else {
+            return false;
+        };
+
+        record == replay
+            || matches!(
+                (fs::canonicalize(record), fs::canonicalize(replay)),
+                (Ok(record), Ok(replay)) if record == replay
+            )
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        self.allowed.is_empty() || self.allowed.iter().any(|entry| matches(entry, ip))
+    }
+
+    /// The role granted by `token`, `None` if the client should be rejected.
+    pub fn role(&self, token: Option<&str>) -> Option<Role> {
+        let Some(operator_token) = &self.operator_token else {
+            return Some(Role::Operator);
+        };
+
+        if token == Some(operator_token.as_str()) {
+            Some(Role::Operator)
+        } else if self.viewer_token.is_none() || token == self.viewer_token.as_deref() {
+            Some(Role::Viewer)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `ip` is the address or in the network `entry`.
+fn matches(entry: &str, ip: IpAddr) -> bool {
+    let (address, prefix) = match entry.split_once('/') {
+        Some((address, prefix)) => (address, prefix.parse::<u32>().ok()),
+        None => (entry, None),
+    };
+
+    let Ok(address) = address.trim().parse::<IpAddr>() else {
+        return false;
+    };
+
+    // Compare IPv4-mapped IPv6 clients as plain IPv4.
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+
+    match (address, ip) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => {
+            let prefix = prefix.unwrap_or(32).min(32);
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(a) & mask == u32::from(b) & mask
+        }
+        (IpAddr::V6(a), IpAddr::V6(b)) => {
+            let prefix = prefix.unwrap_or(128).min(128);
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(a) & mask == u128::from(b) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn matches_addresses_and_networks() {
+        assert!(matches("10.0.11.5", ip("10.0.11.5")));
+        assert!(!matches("10.0.11.5", ip("10.0.11.6")));
+        assert!(matches("10.0.11.0/24", ip("10.0.11.200")));
+        assert!(!matches("10.0.11.0/24", ip("10.0.12.1")));
+        assert!(matches("0.0.0.0/0", ip("192.168.1.1")));
+        assert!(matches("fd00::/8", ip("fd12::1")));
+        assert!(!matches("fd00::/8", ip("fe80::1")));
+    }
+
+    #[test]
+    fn matches_ipv4_mapped_clients() {
+        assert!(matches("127.0.0.1", ip("::ffff:127.0.0.1")));
+        assert!(!matches("::1", ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn ignores_invalid_entries() {
+        assert!(!matches("localhost", ip("127.0.0.1")));
+        assert!(!matches("", ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn roles() {
+        let open = Config::default();
+        assert_eq!(open.role(None), Some(Role::Operator));
+
+        let config = Config {
+            operator_token: Some("secret".to_owned()),
+            viewer_token: Some("watch".to_owned()),
+            ..Config::default()
+        };
+        assert_eq!(config.role(Some("secret")), Some(Role::Operator));
+        assert_eq!(config.role(Some("watch")), Some(Role::Viewer));
+        assert_eq!(config.role(Some("guess")), None);
+        assert_eq!(config.role(None), None);
+    }
+}
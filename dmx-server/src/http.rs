@@ -0,0 +1,269 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+};
+
+/// Longest request head accepted before the connection is dropped.
+const MAX_HEAD: usize = 16 * 1024;
+
+/// A stream with the bytes already read from it put back in front,
+/// so the WebSocket handshake can read the request head again.
+pub struct Replay<S> {
+    head: io::Cursor<Vec<u8>>,
+    stream: S,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Replay<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if (this.head.position() as usize) < this.head.get_ref().len() {
+            Pin::new(&mut this.head).poll_read(cx, buf)
+        } else {
+            Pin::new(&mut this.stream).poll_read(cx, buf)
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Replay<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    headers: Vec<(String, String)>,
+    head: Vec<u8>,
+}
+
+impl Request {
+    /// Reads the request line and headers from `stream`.
+    pub async fn read(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<Self> {
+        let mut head = Vec::new();
+
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() >= MAX_HEAD {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "request too large",
+                ));
+            }
+
+            head.push(stream.read_u8().await?);
+        }
+
+        let text = String::from_utf8_lossy(&head);
+        let mut lines = text.split("\r\n");
+
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_owned();
+        let path = request_line.next().unwrap_or("/").to_owned();
+
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_owned()))
+            .collect();
+
+        Ok(Self {
+            method,
+            path,
+            headers,
+            head,
+        })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.header("upgrade")
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+    }
+
+    /// Gives back the stream with the request head unread.
+    pub fn replay<S>(self, stream: S) -> Replay<S> {
+        Replay {
+            head: io::Cursor::new(self.head),
+            stream,
+        }
+    }
+}
+
+/// Serves the file for `request` from the web client build in `root`.
+pub async fn serve(
+    stream: &mut (impl AsyncWrite + Unpin),
+    request: &Request,
+    root: &Path,
+) -> io::Result<()> {
+    if request.method != "GET" && request.method != "HEAD" {
+        return respond(stream, "405 Method Not Allowed", "text/plain", b"", false).await;
+    }
+
+    let include_body = request.method == "GET";
+
+    let file = match resolve(root, &request.path) {
+        Some(path) => fs::read(&path).await.ok().map(|body| (body, path)),
+        None => None,
+    };
+
+    match file {
+        Some((body, path)) => {
+            respond(stream, "200 OK", content_type(&path), &body, include_body).await
+        }
+        None => {
+            respond(
+                stream,
+                "404 Not Found",
+                "text/plain",
+                b"Not found",
+                include_body,
+            )
+            .await
+        }
+    }
+}
+
+/// Maps a request path to a file under `root`, refusing anything that leaves it.
+fn resolve(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let path = request_path.split(['?', '#']).next().unwrap_or_default();
+    let path = Path::new(path.trim_start_matches('/'));
+
+    let mut file = root.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => file.push(part),
+            Component::CurDir => (),
+            _ => return None,
+        }
+    }
+
+    if file.is_dir() {
+        file.push("index.html");
+    }
+
+    Some(file)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+    {
+        "html" => "text/html; charset=utf-8",
+        "js" => "text/javascript",
+        "wasm" => "application/wasm",
+        "json" => "application/json",
+        "css" => "text/css",
+        "png" => "image/png",
+        "ico" => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+async fn respond(
+    stream: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+    include_body: bool,
+) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+
+    if include_body {
+        stream.write_all(body).await?;
+    }
+
+    stream.flush().await
+}
+
+/// Value of `name` in a query string like `a=1&token=secret`, percent-decoded.
+pub fn query_param(query: &str, name: &str) -> Option<String> {
+    let value = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)?
+        .1;
+
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' if tail.len() >= 2 => {
+                let hex = std::str::from_utf8(&tail[..2]).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            b'+' => {
+                bytes.push(b' ');
+                rest = tail;
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_params() {
+        assert_eq!(
+            query_param("token=secret", "token").as_deref(),
+            Some("secret")
+        );
+        assert_eq!(query_param("a=1&token=x", "token").as_deref(), Some("x"));
+        assert_eq!(query_param("a=1", "token"), None);
+        assert_eq!(query_param("tokens=x", "token"), None);
+    }
+
+    #[test]
+    fn query_params_are_percent_decoded() {
+        assert_eq!(
+            query_param("token=a%2Fb%3D", "token").as_deref(),
+            Some("a/b=")
+        );
+        assert_eq!(query_param("token=a+b", "token").as_deref(), Some("a b"));
+        assert_eq!(query_param("token=%zz", "token"), None);
+        assert_eq!(query_param("token=%C3%A9", "token").as_deref(), Some("é"));
+    }
+}
@@ -1,11 +1,36 @@
-use dmx_shared::DmxMessage;
-use rust_dmx::{available_ports, DmxPort};
-use std::{
+use config::{Config, Role};
+use dmx_shared::{
+    playback::Command,
+    show::{self, ShowError},
+    trigger::TriggerAction,
+    ClientMessage, DmxMessage,
+};
+use futures_util::{SinkExt, StreamExt};
+use http::Request;
+use playback::Hub;
+use recorder::Recorder;
+use std::{env, io, net::SocketAddr, path::PathBuf, sync::Arc};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
     net::{TcpListener, TcpStream},
-    sync::{Arc, Mutex},
-    thread,
+    signal,
+    sync::{broadcast::error::RecvError, watch},
+    task,
+};
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::{
+    self,
+    handshake::server,
+    http::{Response, StatusCode},
 };
 
+mod config;
+mod http;
+mod output;
+mod playback;
+mod recorder;
+mod tls;
+
 // fn dmx(msg: &DmxMessage) -> [u8; 60] {
 //     let mut output = [0; 60];
 
@@ -18,27 +43,206 @@ use std::{
 //     output
 // }
 
-fn handle_client_websocket(stream: TcpStream, handle: Arc<Mutex<DmxHandle>>) {
-    let peer_addr = stream.peer_addr().unwrap();
+async fn handle_client(
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    tls: Option<TlsAcceptor>,
+    hub: Hub,
+    config: Arc<Config>,
+) {
+    if !config.allows(peer_addr.ip()) {
+        println!("Rejected {peer_addr}, not in the allowlist");
+        return;
+    }
+
+    match tls {
+        Some(tls) => match tls.accept(stream).await {
+            Ok(stream) => serve(stream, peer_addr, hub, &config).await,
+            Err(err) => println!("TLS handshake with {peer_addr} failed: {err}"),
+        },
+        None => serve(stream, peer_addr, hub, &config).await,
+    }
+}
+
+/// Serves the web client over HTTP and the DMX WebSocket on the same port.
+async fn serve(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    peer_addr: SocketAddr,
+    hub: Hub,
+    config: &Config,
+) {
+    let request = match Request::read(&mut stream).await {
+        Ok(request) => request,
+        Err(err) => {
+            println!("Invalid request from {peer_addr}: {err}");
+            return;
+        }
+    };
+
+    if request.is_websocket_upgrade() {
+        handle_client_websocket(request.replay(stream), peer_addr, hub, config).await;
+    } else if let Err(err) = http::serve(&mut stream, &request, &config.web_root).await {
+        println!("Failed to serve {} to {peer_addr}: {err}", request.path);
+    }
+}
+
+// The error response type of the handshake callback is set by tungstenite.
+#[allow(clippy::result_large_err)]
+async fn handle_client_websocket(
+    stream: impl AsyncRead + AsyncWrite + Unpin,
+    peer_addr: SocketAddr,
+    hub: Hub,
+    config: &Config,
+) {
+    // Browsers can't set headers on a WebSocket, so the token is passed as `?token=`.
+    let mut role = None;
+    let websocket =
+        tokio_tungstenite::accept_hdr_async(stream, |request: &server::Request, response| {
+            let token = request
+                .uri()
+                .query()
+                .and_then(|query| http::query_param(query, "token"));
+            role = config.role(token.as_deref());
+
+            if role.is_some() {
+                Ok(response)
+            } else {
+                Err(Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(Some("Invalid token".to_owned()))
+                    .unwrap())
+            }
+        })
+        .await;
+
+    let websocket = match websocket {
+        Ok(websocket) => websocket,
+        Err(err) => {
+            println!("Handshake with {peer_addr} failed: {err}");
+            return;
+        }
+    };
 
-    let mut websocket = tungstenite::accept(stream).unwrap();
+    let Some(role) = role else {
+        return;
+    };
 
-    loop {
-        match websocket.read() {
-            Ok(tungstenite::Message::Text(msg)) => {
-                if let Ok(msg) = serde_json::from_str::<DmxMessage>(&msg) {
-                    let mut handle = handle.lock().unwrap();
-                    if let Err(err) = handle.port.write(&msg.buffer[..128]) {
-                        eprintln!("Failed to write to port: {err}");
+    println!("{peer_addr} connected as {role:?}");
+
+    let Some(joined) = hub.join().await else {
+        return;
+    };
+
+    let (mut sink, mut stream) = websocket.split();
+    let mut show = hub.show.clone();
+    let mut commands = joined.commands;
+
+    // Triggers and flash buttons this client is holding, released when it disconnects.
+    let mut held = Vec::new();
+
+    // Every client starts from the show and playback state of the server.
+    let current = show.borrow_and_update().json.clone();
+    let greeting = [current, joined.playback].map(|json| Ok(tungstenite::Message::Text(json)));
+
+    if sink
+        .send_all(&mut futures_util::stream::iter(greeting))
+        .await
+        .is_ok()
+    {
+        loop {
+            let outgoing = tokio::select! {
+                message = stream.next() => match message {
+                    Some(Ok(tungstenite::Message::Text(msg))) => {
+                        // Viewers can watch but their output is ignored.
+                        if role == Role::Operator {
+                            if let Some(msg) = parse(&msg) {
+                                track_held(&mut held, &msg);
+                                hub.messages.send((peer_addr, msg)).await.ok();
+                            }
+                        }
+                        continue;
                     }
-                }
+                    Some(Ok(_)) => continue,
+                    Some(Err(err)) => {
+                        println!("An error occurred, terminating connection with {peer_addr}: {err}",);
+                        break;
+                    }
+                    None => break,
+                },
+                Ok(()) = show.changed() => show.borrow_and_update().clone(),
+                relayed = commands.recv() => match relayed {
+                    Ok(relayed) => relayed,
+                    // A slow client misses some commands rather than holding up the others.
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+            };
+
+            // Clients already have their own edits and commands.
+            if outgoing.origin != Some(peer_addr)
+                && sink
+                    .send(tungstenite::Message::Text(outgoing.json))
+                    .await
+                    .is_err()
+            {
+                break;
             }
-            Ok(_) => (),
-            Err(err) => {
-                println!("An error occurred, terminating connection with {peer_addr}: {err}",);
-                return;
+        }
+    }
+
+    // Don't leave a smoke machine running because a remote dropped while holding it.
+    for release in held {
+        hub.messages
+            .send((peer_addr, ClientMessage::Command(release)))
+            .await
+            .ok();
+    }
+
+    println!("{peer_addr} disconnected");
+}
+
+/// Keeps track of the triggers and flash buttons held by a client, as the commands
+/// that let go of them.
+fn track_held(held: &mut Vec<Command>, msg: &ClientMessage) {
+    let ClientMessage::Command(command) = msg else {
+        return;
+    };
+
+    let (release, down) = match *command {
+        Command::Trigger {
+            target,
+            channel,
+            action,
+        } => {
+            let release = Command::Trigger {
+                target,
+                channel,
+                action: TriggerAction::Release,
+            };
+
+            match action {
+                TriggerAction::Press => (release, true),
+                TriggerAction::Release => (release, false),
+                TriggerAction::Fire => return,
             }
         }
+        Command::Flash { sub, down } => (Command::Flash { sub, down: false }, down),
+        _ => return,
+    };
+
+    held.retain(|h| *h != release);
+    if down {
+        held.push(release);
+    }
+}
+
+fn parse(msg: &str) -> Option<ClientMessage> {
+    match serde_json::from_str(msg) {
+        Ok(msg) => Some(msg),
+        // Clients from before the server could play shows only send frames.
+        Err(_) => serde_json::from_str::<DmxMessage>(msg)
+            .ok()
+            .map(ClientMessage::Frame),
     }
 }
 
@@ -66,33 +270,127 @@ fn handle_client_websocket(stream: TcpStream, handle: Arc<Mutex<DmxHandle>>) {
 //     } {}
 // }
 
-struct DmxHandle {
-    port: Box<dyn DmxPort>,
-}
+#[tokio::main]
+async fn main() {
+    let config_path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| config::DEFAULT_PATH.to_owned());
+    let mut config = Config::load(&config_path)
+        .unwrap_or_else(|e| panic!("Failed to load config {config_path}: {e}"));
+
+    if let Some(web_root) = env::var_os("DMX_WEB_ROOT") {
+        config.web_root = PathBuf::from(web_root);
+    }
+
+    if config.records_over_replay() {
+        panic!("record and replay must be different files");
+    }
+
+    let (frames, frames_receiver) = watch::channel(Vec::new());
+    let (shutdown, shutdown_receiver) = std::sync::mpsc::channel();
+    let output = output::spawn(frames_receiver, shutdown_receiver)
+        .unwrap_or_else(|e| panic!("Failed to open port: {e}"));
+
+    let recorder = match &config.record {
+        Some(path) => {
+            let recorder = Recorder::start(path, frames.subscribe())
+                .await
+                .unwrap_or_else(|e| panic!("Failed to record to {}: {e}", path.display()));
+            println!("Recording output to {}", path.display());
+            Some(recorder)
+        }
+        None => None,
+    };
+
+    match config.replay.clone() {
+        Some(path) => {
+            println!("Replaying {}", path.display());
+            tokio::select! {
+                result = recorder::replay(&path, &frames, config.replay_loop) => match result {
+                    Ok(()) => println!("Replay finished"),
+                    Err(e) => println!("Failed to replay {}: {e}", path.display()),
+                },
+                _ = signal::ctrl_c() => println!("Shutting down"),
+            }
+        }
+        None => serve_clients(config, frames).await,
+    }
+
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.stop().await {
+            println!("Failed to write the recording: {e}");
+        }
+    }
 
-unsafe impl Send for DmxHandle {}
+    shutdown.send(()).ok();
+    task::spawn_blocking(move || output.join()).await.ok();
+}
 
-fn main() {
-    let listener = TcpListener::bind("0.0.0.0:33333").unwrap();
+/// Accepts clients and plays their show to `frames` until Ctrl+C.
+async fn serve_clients(config: Config, frames: watch::Sender<Vec<u8>>) {
+    let listener = TcpListener::bind("0.0.0.0:33333").await.unwrap();
     println!("Server listening on port 33333");
 
-    let port = Arc::new(Mutex::new({
-        let mut ports = available_ports().unwrap();
-        let mut port = ports.remove(1);
-        port.open().unwrap();
-        DmxHandle { port }
-    }));
-
-    for stream in listener.incoming() {
-        let port = port.clone();
-        match stream {
-            Ok(stream) => {
-                println!("New connection: {}", stream.peer_addr().unwrap());
-                thread::spawn(move || handle_client_websocket(stream, port));
+    println!("Serving web client from {}", config.web_root.display());
+    if config.operator_token.is_none() {
+        println!("No operator token set, every client can drive the rig");
+    }
+
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            let tls = tls::load(cert, key)
+                .unwrap_or_else(|e| panic!("Failed to load TLS certificate: {e}"));
+            println!("TLS enabled, clients connect with https:// and wss://");
+            Some(TlsAcceptor::from(tls))
+        }
+        (None, None) => None,
+        _ => panic!("tls_cert and tls_key must be set together"),
+    };
+
+    let show = config
+        .show
+        .as_ref()
+        .and_then(|path| match show::load(path) {
+            Ok(show) => {
+                println!("Playing show {}", path.display());
+                Some(show)
+            }
+            Err(ShowError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                println!(
+                    "No show at {} yet, waiting for a client to upload one",
+                    path.display()
+                );
+                None
             }
-            Err(e) => {
-                println!("Error: {}", e);
+            Err(e) => panic!("Failed to load show {}: {e}", path.display()),
+        });
+
+    let config = Arc::new(config);
+
+    let hub = playback::spawn(show, config.show.clone(), frames);
+
+    let accept = async {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer_addr)) => {
+                    println!("New connection: {peer_addr}");
+                    tokio::spawn(handle_client(
+                        stream,
+                        peer_addr,
+                        tls.clone(),
+                        hub.clone(),
+                        config.clone(),
+                    ));
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                }
             }
         }
+    };
+
+    tokio::select! {
+        _ = accept => (),
+        _ = signal::ctrl_c() => println!("Shutting down"),
     }
 }
@@ -0,0 +1,95 @@
+use rust_dmx::{available_ports, DmxPort};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+/// Number of channels written to the port.
+pub const CHANNELS: usize = 128;
+
+/// The port is refreshed at this interval even when the frame doesn't change,
+/// some fixtures fall back to their own programs when the signal stops.
+pub const INTERVAL: Duration = Duration::from_millis(25);
+
+/// Starts the thread that owns the port and writes the latest frame from `frames`
+/// at a steady rate. The rig is blacked out and the thread exits when `shutdown`
+/// receives or is dropped.
+///
+/// `DmxPort` isn't `Send`, so the port is opened on the thread and never leaves it.
+pub fn spawn(
+    frames: watch::Receiver<Vec<u8>>,
+    shutdown: mpsc::Receiver<()>,
+) -> Result<JoinHandle<()>, String> {
+    let (ready, opened) = mpsc::sync_channel(1);
+
+    let thread = thread::spawn(move || match open() {
+        Ok(port) => {
+            ready.send(Ok(())).ok();
+            run(port, frames, shutdown);
+        }
+        Err(err) => {
+            ready.send(Err(err)).ok();
+        }
+    });
+
+    match opened.recv() {
+        Ok(Ok(())) => Ok(thread),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err("output thread exited".to_owned()),
+    }
+}
+
+fn open() -> Result<Box<dyn DmxPort>, String> {
+    let mut ports = available_ports().map_err(|e| format!("{e:?}"))?;
+
+    if ports.len() < 2 {
+        return Err(format!("expected at least 2 ports, found {}", ports.len()));
+    }
+
+    let mut port = ports.remove(1);
+    port.open().map_err(|e| format!("{e:?}"))?;
+
+    Ok(port)
+}
+
+fn run(mut port: Box<dyn DmxPort>, frames: watch::Receiver<Vec<u8>>, shutdown: mpsc::Receiver<()>) {
+    let mut next = Instant::now();
+    let mut failing = false;
+
+    loop {
+        let mut frame = frames.borrow().clone();
+        frame.resize(CHANNELS, 0);
+
+        // Only report when writes start or stop failing, not on every refresh.
+        match port.write(&frame) {
+            Ok(()) if failing => {
+                println!("Port recovered");
+                failing = false;
+            }
+            Ok(()) => (),
+            Err(err) if !failing => {
+                eprintln!("Failed to write to port: {err}");
+                failing = true;
+            }
+            Err(_) => (),
+        }
+
+        next += INTERVAL;
+        let now = Instant::now();
+        if next < now {
+            // Fell behind, don't try to catch up with a burst of writes.
+            next = now;
+        }
+
+        match shutdown.recv_timeout(next - now) {
+            Err(RecvTimeoutError::Timeout) => (),
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    if let Err(err) = port.write(&[0; CHANNELS]) {
+        eprintln!("Failed to black out: {err}");
+    }
+}
@@ -0,0 +1,213 @@
+use dmx_shared::{
+    playback::{Command, Playback},
+    show::{self, Show},
+    ClientMessage, ServerMessage,
+};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs,
+    sync::{broadcast, mpsc, oneshot, watch},
+    time::{self, Instant, MissedTickBehavior},
+};
+
+use crate::output;
+
+/// Commands relayed to other clients are dropped for clients this far behind.
+const RELAY_CAPACITY: usize = 256;
+
+/// Clients wait for the playback task once this many of their messages are queued.
+const MESSAGE_CAPACITY: usize = 64;
+
+/// A message for clients and the client it came from, which doesn't get it back.
+#[derive(Clone)]
+pub struct Relayed {
+    /// `None` for changes made by the server itself.
+    pub origin: Option<SocketAddr>,
+    /// Serialized `ServerMessage`.
+    pub json: String,
+}
+
+/// What a client that connects starts from, besides the show.
+pub struct Joined {
+    /// Serialized `ServerMessage::Playback`.
+    pub playback: String,
+    /// Commands from operators applied after the playback state was taken, applied by
+    /// the client to keep its preview in step.
+    pub commands: broadcast::Receiver<Relayed>,
+}
+
+/// Channels between the client connections and the playback task.
+#[derive(Clone)]
+pub struct Hub {
+    /// Messages from operators with the address of the client that sent them.
+    pub messages: mpsc::Sender<(SocketAddr, ClientMessage)>,
+    /// The current show, every client is sent it on connect and after every edit.
+    pub show: watch::Receiver<Relayed>,
+    joins: mpsc::Sender<oneshot::Sender<Joined>>,
+}
+
+impl Hub {
+    /// The playback state for a client that connects, `None` once the playback task stopped.
+    pub async fn join(&self) -> Option<Joined> {
+        let (sender, joined) = oneshot::channel();
+        self.joins.send(sender).await.ok()?;
+        joined.await.ok()
+    }
+}
+
+/// Starts the task that feeds `frames` from the messages of all clients. Frames streamed
+/// by clients are output as is until there is a show, from then on the server renders the
+/// universe itself, holds the show for all clients, and clients act as editors and remotes.
+///
+/// `show` is the show loaded at startup, uploaded shows are saved to `path`.
+pub fn spawn(show: Option<Show>, path: Option<PathBuf>, frames: watch::Sender<Vec<u8>>) -> Hub {
+    let (messages, messages_receiver) = mpsc::channel(MESSAGE_CAPACITY);
+    let (show_sender, show_receiver) = watch::channel(relayed(None, &show_message(&show)));
+    let (joins, joins_receiver) = mpsc::channel(MESSAGE_CAPACITY);
+
+    let saves = path.map(|path| {
+        let (saves, saves_receiver) = watch::channel(None);
+        tokio::spawn(save_uploads(path, saves_receiver));
+        saves
+    });
+
+    tokio::spawn(run(
+        show,
+        saves,
+        messages_receiver,
+        joins_receiver,
+        frames,
+        show_sender,
+    ));
+
+    Hub {
+        messages,
+        show: show_receiver,
+        joins,
+    }
+}
+
+async fn run(
+    mut show: Option<Show>,
+    saves: Option<watch::Sender<Option<Show>>>,
+    mut messages: mpsc::Receiver<(SocketAddr, ClientMessage)>,
+    mut joins: mpsc::Receiver<oneshot::Sender<Joined>>,
+    frames: watch::Sender<Vec<u8>>,
+    shows: watch::Sender<Relayed>,
+) {
+    let (commands, _) = broadcast::channel(RELAY_CAPACITY);
+    let mut playback = Playback::default();
+    let mut ignoring_frames = false;
+
+    let mut interval = time::interval(output::INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_tick = Instant::now();
+
+    loop {
+        tokio::select! {
+            now = interval.tick() => {
+                let dt = now.saturating_duration_since(last_tick).as_secs_f32();
+                last_tick = now;
+
+                if let Some(show) = &mut show {
+                    frames.send_replace(playback.frame(show, dt).buffer);
+                }
+            }
+            Some(join) = joins.recv() => {
+                let state = match &show {
+                    Some(show) => playback.state(show),
+                    None => playback.state(&Show::default()),
+                };
+
+                // Subscribed here so the client gets exactly the commands after the state.
+                join.send(Joined {
+                    playback: relayed(None, &ServerMessage::Playback(state)).json,
+                    commands: commands.subscribe(),
+                })
+                .ok();
+            }
+            message = messages.recv() => match message {
+                Some((_, ClientMessage::Frame(frame))) => {
+                    if show.is_none() {
+                        frames.send_replace(frame.buffer);
+                    } else if !ignoring_frames {
+                        println!("Ignoring frames from clients while the server plays the show");
+                        ignoring_frames = true;
+                    }
+                }
+                Some((origin, ClientMessage::Show(mut uploaded))) => {
+                    if let Err(e) = uploaded.validate() {
+                        println!("Rejected a show from {origin}: {e}");
+                        // Puts the uploader back on the show that is still playing.
+                        shows.send_replace(relayed(None, &show_message(&show)));
+                        continue;
+                    }
+
+                    uploaded.rehydrate();
+                    if let Some(previous) = &show {
+                        uploaded.carry_over(previous);
+                    }
+
+                    if let Some(saves) = &saves {
+                        saves.send_replace(Some(uploaded.clone()));
+                    }
+
+                    show = Some(uploaded);
+                    shows.send_replace(relayed(Some(origin), &show_message(&show)));
+                }
+                Some((origin, ClientMessage::Command(command))) => {
+                    let Some(show) = &mut show else {
+                        continue;
+                    };
+
+                    // Tapping changes the tempo of the show, which the tapping client
+                    // uploads, other clients would time the taps differently.
+                    if !matches!(command, Command::Tap) {
+                        let message = ServerMessage::Command(command.clone());
+                        commands.send(relayed(Some(origin), &message)).ok();
+                    }
+
+                    playback.apply(show, command);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+fn show_message(show: &Option<Show>) -> ServerMessage {
+    ServerMessage::Show(show.clone())
+}
+
+fn relayed(origin: Option<SocketAddr>, message: &ServerMessage) -> Relayed {
+    Relayed {
+        origin,
+        json: serde_json::to_string(message).unwrap_or_default(),
+    }
+}
+
+/// Saves the uploaded shows sent to `shows`. Uploads that come in faster than the disk
+/// keeps up with are skipped, only the latest one is saved.
+async fn save_uploads(path: PathBuf, mut shows: watch::Receiver<Option<Show>>) {
+    while shows.changed().await.is_ok() {
+        let show = shows.borrow_and_update().clone();
+
+        if let Some(show) = show {
+            save(&path, &show).await;
+        }
+    }
+}
+
+async fn save(path: &Path, show: &Show) {
+    let result = match show::to_json(show) {
+        Ok(json) => fs::write(path, json).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    if let Err(e) = result {
+        println!("Failed to save the show to {}: {e}", path.display());
+    }
+}
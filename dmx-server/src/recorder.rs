@@ -0,0 +1,112 @@
+use dmx_shared::recording::{self, Frame};
+use std::{io, path::Path};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+    sync::{oneshot, watch},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+use crate::output;
+
+/// Writes every change of the output to a recording file until stopped.
+pub struct Recorder {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl Recorder {
+    /// Starts recording the frames sent to the port to `path`, which is overwritten.
+    /// Only the channels written to the port are kept, and only frames that changed.
+    pub async fn start(path: &Path, mut frames: watch::Receiver<Vec<u8>>) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path).await?);
+
+        let mut encoded = Vec::new();
+        recording::encode_header(&mut encoded);
+        file.write_all(&encoded).await?;
+
+        let (stop, mut stopped) = oneshot::channel();
+
+        let task = tokio::spawn(async move {
+            let start = Instant::now();
+            let mut last = None;
+
+            loop {
+                tokio::select! {
+                    changed = frames.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+
+                        let buffer = {
+                            let frame = frames.borrow_and_update();
+                            frame[..frame.len().min(output::CHANNELS)].to_vec()
+                        };
+
+                        if last.as_ref() == Some(&buffer) {
+                            continue;
+                        }
+
+                        let frame = Frame {
+                            time: start.elapsed(),
+                            buffer,
+                        };
+
+                        encoded.clear();
+                        recording::encode_frame(&mut encoded, &frame);
+                        file.write_all(&encoded).await?;
+                        last = Some(frame.buffer);
+                    }
+                    _ = &mut stopped => break,
+                }
+            }
+
+            // Marks the end, so a replay holds the last frame as long as it was output.
+            if let Some(buffer) = last {
+                encoded.clear();
+                recording::encode_frame(
+                    &mut encoded,
+                    &Frame {
+                        time: start.elapsed(),
+                        buffer,
+                    },
+                );
+                file.write_all(&encoded).await?;
+            }
+
+            file.flush().await
+        });
+
+        Ok(Self { stop, task })
+    }
+
+    /// Stops recording and flushes the file, returns the first error writing it.
+    pub async fn stop(self) -> io::Result<()> {
+        self.stop.send(()).ok();
+        self.task.await.map_err(io::Error::other)?
+    }
+}
+
+/// Sends the frames of the recording at `path` to `frames` with their original timing,
+/// over and over if `looping`. Returns when the recording ends.
+pub async fn replay(path: &Path, frames: &watch::Sender<Vec<u8>>, looping: bool) -> io::Result<()> {
+    let recorded = recording::decode(&fs::read(path).await?)?;
+
+    if recorded.is_empty() {
+        return Ok(());
+    }
+
+    loop {
+        let start = Instant::now();
+
+        for frame in &recorded {
+            time::sleep_until(start + frame.time).await;
+            frames.send_replace(frame.buffer.clone());
+        }
+
+        if !looping {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,520 @@
+use emath::{Pos2, Rect};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ui")]
+use egui::{
+    epaint::{self, Color32, PathShape, Shape, Stroke},
+    Button, DragValue, Id, PointerButton, Sense, Ui, Vec2, Widget,
+};
+
+/// Snapping applied to control points while they are added or dragged.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Grid {
+    pub enabled: bool,
+    /// Step on the time axis, 0..1000.
+    pub time: f32,
+    /// Step on the value axis, 0..1.
+    pub value: f32,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time: 50.0,
+            value: 0.1,
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Grid {
+    fn snap(&self, point: Pos2) -> Pos2 {
+        if !self.enabled {
+            return point;
+        }
+
+        let value = snap(1.0 - point.y / 100.0, self.value);
+
+        Pos2::new(snap(point.x, self.time), (1.0 - value) * 100.0)
+    }
+}
+
+#[cfg(feature = "ui")]
+fn snap(value: f32, step: f32) -> f32 {
+    if step > 0.0 {
+        (value / step).round() * step
+    } else {
+        value
+    }
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+/// Curve drawn with control points. The editing state is only used by the ui.
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub struct ChannelWidget {
+    next_id: i32,
+    control_points: Vec<(Pos2, i32)>,
+    #[serde(default)]
+    grid: Grid,
+    /// Looping curves wrap around and keep the first and last point at the same value.
+    #[serde(default = "default_looping")]
+    looping: bool,
+
+    #[serde(skip)]
+    selected: Vec<i32>,
+    /// Where the box selection drag started and where the pointer is now.
+    #[serde(skip)]
+    box_selection: Option<(Pos2, Pos2)>,
+}
+
+impl Default for ChannelWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChannelWidget {
+    pub fn new() -> Self {
+        Self {
+            next_id: 3,
+            control_points: vec![
+                (Pos2::new(0.0, 0.0), 0),
+                (Pos2::new(100.0, 64.0), 1),
+                (Pos2::new(1000.0, 0.0), 2),
+            ],
+            grid: Grid::default(),
+            looping: true,
+            selected: Vec::new(),
+            box_selection: None,
+        }
+    }
+
+    /// A non looping curve mapping 0..1 linearly to 0..1, for use as a lookup table.
+    pub fn lut() -> Self {
+        Self {
+            next_id: 2,
+            control_points: vec![(Pos2::new(0.0, 100.0), 0), (Pos2::new(1000.0, 0.0), 1)],
+            grid: Grid::default(),
+            looping: false,
+            selected: Vec::new(),
+            box_selection: None,
+        }
+    }
+
+    /// Evaluates the curve at `time` (0..1000, wrapping if looping) and returns a value in 0..1.
+    pub fn value(&self, time: f32) -> f32 {
+        if !self.looping && time >= 1000.0 {
+            let last = self.control_points[self.control_points.len() - 1].0;
+            return 1.0 - last.y / 100.0;
+        }
+
+        let time = if self.looping {
+            time % 1000.0
+        } else {
+            time.max(0.0)
+        };
+
+        let mut before = Pos2::new(0.0, 100.0);
+        let mut after = Pos2::new(1000.0, 100.0);
+
+        for points in self.control_points.windows(2) {
+            if points[0].0.x <= time && points[1].0.x > time {
+                before = points[0].0;
+                after = points[1].0;
+            }
+        }
+
+        let range = after.x - before.x;
+        let pos = time - before.x;
+
+        let ratio = pos / range;
+
+        let y = before.y * (1.0 - ratio) + after.y * ratio;
+
+        1.0 - y / 100.0
+    }
+}
+
+#[cfg(feature = "ui")]
+impl ChannelWidget {
+    pub fn ui(&mut self, ui: &mut Ui, time: f32) -> f32 {
+        let time = if self.looping {
+            time % 1000.0
+        } else {
+            time.clamp(0.0, 1000.0)
+        };
+
+        let (response, painter) =
+            ui.allocate_painter(Vec2::new(1000.0, 100.0), Sense::click_and_drag());
+
+        let to_screen = emath::RectTransform::from_to(
+            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
+            response.rect,
+        );
+
+        let to_painter = emath::RectTransform::from_to(
+            response.rect,
+            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
+        );
+
+        let extend_selection = ui.input(|i| i.modifiers.shift || i.modifiers.command);
+
+        if response.clicked() {
+            if let Some(pos) = response.hover_pos() {
+                let point = self.grid.snap(to_painter.transform_pos(pos));
+
+                self.control_points.push((point, self.next_id));
+                self.selected = vec![self.next_id];
+                self.next_id += 1;
+                self.normalize();
+            }
+        }
+
+        if let Some(pos) = response.interact_pointer_pos() {
+            let pos = to_painter.transform_pos(pos);
+
+            if response.drag_started() {
+                self.box_selection = Some((pos, pos));
+            } else if let Some((_, end)) = &mut self.box_selection {
+                *end = pos;
+            }
+        }
+
+        if response.drag_released() {
+            if let Some((start, end)) = self.box_selection.take() {
+                let selection = Rect::from_two_pos(start, end);
+
+                if !extend_selection {
+                    self.selected.clear();
+                }
+
+                for (point, id) in &self.control_points {
+                    if selection.contains(*point) && !self.selected.contains(id) {
+                        self.selected.push(*id);
+                    }
+                }
+            }
+        }
+
+        painter.add(epaint::RectShape::stroke(
+            response.rect,
+            0.0,
+            Stroke::new(2.0, Color32::LIGHT_GREEN.linear_multiply(0.25)),
+        ));
+
+        let mut remove_list = Vec::new();
+        let mut strokes = Vec::new();
+        let mut clicked = None;
+        let mut grabbed = None;
+
+        let control_point_radius = 5.0;
+        let points = self.control_points.len();
+
+        for (index, (point, id)) in self.control_points.iter().enumerate() {
+            let size = Vec2::splat(2.0 * control_point_radius);
+
+            let point_in_screen = to_screen.transform_pos(*point);
+            let point_rect = Rect::from_center_size(point_in_screen, size);
+            let point_id = response.id.with(*id);
+            let point_response = ui.interact(point_rect, point_id, Sense::click_and_drag());
+
+            if point_response.clicked()
+                || (point_response.drag_started() && !self.selected.contains(id))
+            {
+                clicked = Some(*id);
+            }
+
+            if point_response.dragged() {
+                if let Some(pos) = point_response.interact_pointer_pos() {
+                    grabbed = Some((*point, to_painter.transform_pos(pos)));
+                }
+            }
+
+            if index != 0
+                && index != points - 1
+                && point_response.clicked_by(PointerButton::Secondary)
+            {
+                remove_list.push(*id);
+            }
+
+            strokes.push(ui.style().interact(&point_response).fg_stroke);
+        }
+
+        if let Some(id) = clicked {
+            if !extend_selection {
+                self.selected.clear();
+            }
+
+            if let Some(index) = self.selected.iter().position(|selected| *selected == id) {
+                self.selected.remove(index);
+            } else {
+                self.selected.push(id);
+            }
+        }
+
+        if let Some((from, to)) = grabbed {
+            self.move_selected(self.grid.snap(to) - from);
+        }
+
+        self.remove(&remove_list);
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.grid.enabled, "Snap");
+            ui.label("Time grid");
+            DragValue::new(&mut self.grid.time)
+                .clamp_range(1.0..=1000.0)
+                .speed(1.0)
+                .ui(ui);
+            ui.label("Value grid");
+            DragValue::new(&mut self.grid.value)
+                .clamp_range(0.01..=1.0)
+                .speed(0.01)
+                .ui(ui);
+
+            ui.separator();
+
+            let clipboard_id = Id::new("channel_clipboard");
+            let clipboard = ui.data(|d| d.get_temp::<Vec<Pos2>>(clipboard_id));
+
+            if ui
+                .add_enabled(!self.selected.is_empty(), Button::new("Copy"))
+                .clicked()
+            {
+                let copied = self.copy();
+                ui.data_mut(|d| d.insert_temp(clipboard_id, copied));
+            }
+
+            if ui
+                .add_enabled(clipboard.is_some(), Button::new("Paste"))
+                .clicked()
+            {
+                if let Some(clipboard) = clipboard {
+                    self.paste(&clipboard);
+                }
+            }
+
+            if ui
+                .add_enabled(!self.selected.is_empty(), Button::new("Delete"))
+                .clicked()
+            {
+                let selected = self.selected.clone();
+                self.remove(&selected);
+            }
+        });
+
+        self.inspector_ui(ui);
+
+        self.normalize();
+
+        for ((point, id), stroke) in self.control_points.iter().zip(strokes) {
+            let point_in_screen = to_screen.transform_pos(*point);
+            let stroke = if self.selected.contains(id) {
+                Stroke::new(stroke.width.max(2.0), Color32::LIGHT_BLUE)
+            } else {
+                stroke
+            };
+
+            painter.add(Shape::circle_stroke(
+                point_in_screen,
+                control_point_radius,
+                stroke,
+            ));
+        }
+
+        if let Some((start, end)) = self.box_selection {
+            painter.add(epaint::RectShape::stroke(
+                to_screen.transform_rect(Rect::from_two_pos(start, end)),
+                0.0,
+                Stroke::new(1.0, Color32::LIGHT_BLUE.linear_multiply(0.5)),
+            ));
+        }
+
+        let value = self.value(time);
+
+        let pos = to_screen * Pos2::new(time, (1.0 - value) * 100.0);
+
+        painter.add(Shape::circle_stroke(
+            pos,
+            control_point_radius,
+            Stroke::new(1.0, Color32::RED.linear_multiply(0.25)),
+        ));
+
+        {
+            let points_in_screen: Vec<Pos2> = self
+                .control_points
+                .iter()
+                .map(|p| to_screen * p.0)
+                .collect();
+            painter.add(PathShape::line(
+                points_in_screen,
+                Stroke::new(2.0, Color32::RED.linear_multiply(0.25)),
+            ));
+        }
+
+        {
+            let points_in_screen: Vec<Pos2> = [Pos2::new(time, 0.0), Pos2::new(time, 100.0)]
+                .iter()
+                .map(|p| to_screen * *p)
+                .collect();
+
+            painter.add(PathShape::line(
+                points_in_screen,
+                Stroke::new(2.0, Color32::WHITE.linear_multiply(0.25)),
+            ));
+        }
+
+        value
+    }
+
+    fn inspector_ui(&mut self, ui: &mut Ui) {
+        let [id] = self.selected[..] else {
+            if !self.selected.is_empty() {
+                ui.label(format!("{} points selected", self.selected.len()));
+            }
+            return;
+        };
+
+        let last = self.control_points.len() - 1;
+        let Some(index) = self.control_points.iter().position(|(_, i)| *i == id) else {
+            return;
+        };
+
+        let is_endpoint = index == 0 || index == last;
+        let point = &mut self.control_points[index].0;
+        let mut value = 1.0 - point.y / 100.0;
+
+        ui.horizontal(|ui| {
+            ui.label("Time");
+            ui.add_enabled(
+                !is_endpoint,
+                DragValue::new(&mut point.x)
+                    .clamp_range(0.0..=1000.0)
+                    .speed(1.0),
+            );
+            ui.label("Value");
+            if DragValue::new(&mut value)
+                .clamp_range(0.0..=1.0)
+                .speed(0.01)
+                .ui(ui)
+                .changed()
+            {
+                point.y = (1.0 - value) * 100.0;
+            }
+        });
+
+        if is_endpoint && self.looping {
+            let y = self.control_points[index].0.y;
+            self.control_points[0].0.y = y;
+            self.control_points[last].0.y = y;
+        }
+    }
+
+    fn move_selected(&mut self, delta: Vec2) {
+        let last = self.control_points.len() - 1;
+        let bounds = Rect::from_min_size(Pos2::ZERO, Vec2::new(1000.0, 100.0));
+
+        let mut first_edited = false;
+        let mut last_edited = false;
+
+        for (index, (point, id)) in self.control_points.iter_mut().enumerate() {
+            if !self.selected.contains(id) {
+                continue;
+            }
+
+            let mut delta = delta;
+
+            if index == 0 || index == last {
+                delta.x = 0.0;
+            }
+
+            if index == 0 {
+                first_edited = true;
+            }
+            if index == last {
+                last_edited = true;
+            }
+
+            *point = bounds.clamp(*point + delta);
+        }
+
+        if !self.looping {
+            return;
+        }
+
+        if first_edited {
+            self.control_points[last].0.y = self.control_points[0].0.y;
+        } else if last_edited {
+            self.control_points[0].0.y = self.control_points[last].0.y;
+        }
+    }
+
+    /// Removes the given points, the first and last point are always kept.
+    fn remove(&mut self, ids: &[i32]) {
+        let first = self.control_points[0].1;
+        let last = self.control_points[self.control_points.len() - 1].1;
+
+        self.control_points
+            .retain(|(_, id)| *id == first || *id == last || !ids.contains(id));
+        self.selected
+            .retain(|id| *id == first || *id == last || !ids.contains(id));
+    }
+
+    /// Returns the selected points ordered by time.
+    fn copy(&self) -> Vec<Pos2> {
+        self.control_points
+            .iter()
+            .filter(|(_, id)| self.selected.contains(id))
+            .map(|(point, _)| *point)
+            .collect()
+    }
+
+    /// Replaces the points in the time range covered by `points` with `points`.
+    fn paste(&mut self, points: &[Pos2]) {
+        let (Some(start), Some(end)) = (points.first(), points.last()) else {
+            return;
+        };
+
+        let (start, end) = (start.x, end.x);
+        let last = self.control_points.len() - 1;
+
+        let mut index = 0;
+        self.control_points.retain(|(point, _)| {
+            let keep = index == 0 || index == last || point.x < start || point.x > end;
+            index += 1;
+            keep
+        });
+
+        self.selected.clear();
+
+        for point in points {
+            if point.x <= 0.0 || point.x >= 1000.0 {
+                let last = self.control_points.len() - 1;
+                if self.looping || point.x <= 0.0 {
+                    self.control_points[0].0.y = point.y;
+                }
+                if self.looping || point.x >= 1000.0 {
+                    self.control_points[last].0.y = point.y;
+                }
+            } else {
+                self.control_points.push((*point, self.next_id));
+                self.selected.push(self.next_id);
+                self.next_id += 1;
+            }
+        }
+
+        self.normalize();
+    }
+
+    fn normalize(&mut self) {
+        self.control_points.sort_by(|a, b| a.0.x.total_cmp(&b.0.x));
+
+        let len = self.control_points.len();
+        self.control_points[0].0.x = 0.0;
+        self.control_points[len - 1].0.x = 1000.0;
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(feature = "ui")]
+use egui::{DragValue, Ui, Widget};
+
+#[cfg(feature = "ui")]
+use crate::playback::Command;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Cycle lengths in beats that a device can be synced to.
+pub const BEAT_DIVISIONS: [f32; 8] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0];
+
+pub fn default_bpm() -> f32 {
+    120.0
+}
+
+pub fn format_beats(beats: f32) -> String {
+    if beats < 1.0 {
+        format!("1/{} beat", (1.0 / beats).round())
+    } else if beats == 1.0 {
+        "1 beat".to_string()
+    } else {
+        format!("{beats} beats")
+    }
+}
+
+/// Master show clock, devices synced to it derive their time from `beats`
+/// so they never drift apart.
+#[derive(Default)]
+pub struct Clock {
+    /// Position in beats since the last resync.
+    pub beats: f64,
+    taps: Vec<Instant>,
+}
+
+impl Clock {
+    pub fn advance(&mut self, bpm: f32, dt: f32) {
+        self.beats += bpm as f64 / 60.0 * dt as f64;
+    }
+
+    pub fn resync(&mut self) {
+        self.beats = 0.0;
+    }
+
+    /// Registers a tap and returns the tapped tempo once there are enough taps.
+    /// Every tap also moves the clock to the nearest beat.
+    pub fn tap(&mut self, now: Instant) -> Option<f32> {
+        if let Some(last) = self.taps.last() {
+            if now.saturating_duration_since(*last).as_secs_f32() > 2.0 {
+                self.taps.clear();
+            }
+        }
+
+        self.taps.push(now);
+
+        if self.taps.len() > 8 {
+            self.taps.remove(0);
+        }
+
+        self.beats = self.beats.round();
+
+        if self.taps.len() < 2 {
+            return None;
+        }
+
+        let span = now.saturating_duration_since(self.taps[0]).as_secs_f32();
+
+        Some(60.0 * (self.taps.len() - 1) as f32 / span)
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Clock {
+    /// Tap and resync are returned as commands so they can be sent to the server.
+    pub fn ui(&self, ui: &mut Ui, bpm: &mut f32) -> Option<Command> {
+        let mut command = None;
+
+        ui.horizontal(|ui| {
+            ui.label("BPM");
+            DragValue::new(bpm)
+                .clamp_range(20.0..=300.0)
+                .speed(0.1)
+                .max_decimals(1)
+                .ui(ui);
+
+            if ui.button("Tap").clicked() {
+                command = Some(Command::Tap);
+            }
+
+            if ui.button("Resync All").clicked() {
+                command = Some(Command::Resync);
+            }
+
+            ui.label(format!("Beat {}", self.beats.floor() as u64 % 4 + 1));
+        });
+
+        command
+    }
+}
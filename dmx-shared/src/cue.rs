@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+
+use crate::dmx_device::{ChannelKind, Layout};
+
+#[cfg(feature = "ui")]
+use crate::playback::Command;
+#[cfg(feature = "ui")]
+use egui::{Button, CollapsingHeader, DragValue, Ui, Widget};
+
+/// A stored look of the universe.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Cue {
+    pub name: String,
+    /// Fade time in seconds for channels going up.
+    pub fade_in: f32,
+    /// Fade time in seconds for channels going down.
+    pub fade_out: f32,
+    /// Wait time in seconds between GO and the start of the fade.
+    pub delay: f32,
+    buffer: Vec<u8>,
+    /// Channels controlled by the cue, the others are left to the timelines.
+    mask: Vec<bool>,
+}
+
+impl Cue {
+    #[cfg(feature = "ui")]
+    fn record(name: String, buffer: &[u8], mask: Vec<bool>) -> Self {
+        Self {
+            name,
+            fade_in: 1.0,
+            fade_out: 1.0,
+            delay: 0.0,
+            buffer: buffer.to_vec(),
+            mask,
+        }
+    }
+
+    fn controls(&self, channel: usize) -> bool {
+        self.mask.get(channel).copied().unwrap_or(false)
+    }
+}
+
+struct Fade {
+    /// Output at the time of GO.
+    from: Vec<u8>,
+    /// Channels that were controlled by cues at the time of GO.
+    from_mask: Vec<bool>,
+    elapsed: f32,
+    fade_in: f32,
+    fade_out: f32,
+    delay: f32,
+}
+
+/// Plays back the cue list on top of the timeline output.
+#[derive(Default)]
+pub struct CuePlayer {
+    current: Option<usize>,
+    fade: Option<Fade>,
+    output: Vec<u8>,
+    controlled: Vec<bool>,
+}
+
+impl CuePlayer {
+    pub fn go(&mut self, cues: &[Cue]) {
+        let next = self.current.map_or(0, |current| current + 1);
+        if next < cues.len() {
+            self.start(cues, Some(next));
+        }
+    }
+
+    pub fn back(&mut self, cues: &[Cue]) {
+        if let Some(current) = self.current.filter(|current| *current > 0) {
+            self.start(cues, Some(current - 1));
+        }
+    }
+
+    /// Fades out the current cue, handing all channels back to the timelines.
+    pub fn release(&mut self, cues: &[Cue]) {
+        if self.current.is_some() {
+            self.start(cues, None);
+        }
+    }
+
+    /// The cue being played, `None` when released.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Moves to cue `current` without fading.
+    pub fn jump(&mut self, current: Option<usize>) {
+        self.current = current;
+        self.fade = None;
+    }
+
+    fn start(&mut self, cues: &[Cue], next: Option<usize>) {
+        let (fade_in, fade_out, delay) = match next.and_then(|next| cues.get(next)) {
+            Some(cue) => (cue.fade_in, cue.fade_out, cue.delay),
+            None => {
+                let fade_out = self
+                    .current
+                    .and_then(|current| cues.get(current))
+                    .map_or(0.0, |cue| cue.fade_out);
+                (fade_out, fade_out, 0.0)
+            }
+        };
+
+        self.fade = Some(Fade {
+            from: self.output.clone(),
+            from_mask: self.controlled.clone(),
+            elapsed: 0.0,
+            fade_in,
+            fade_out,
+            delay,
+        });
+        self.current = next;
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Overlays the cue output on `buffer`, which holds the timeline output.
+    /// Control parameters jump to their new value once the delay has passed.
+    pub fn apply(&mut self, cues: &[Cue], layout: &Layout, buffer: &mut [u8], dt: f32) {
+        let target = self
+            .current
+            .and_then(|current| cues.get(current))
+            .filter(|cue| cue.buffer.len() >= buffer.len());
+
+        if target.is_none() && self.current.is_some() {
+            self.current = None;
+        }
+
+        self.controlled.clear();
+        self.controlled.resize(buffer.len(), false);
+
+        if let Some(fade) = &mut self.fade {
+            fade.elapsed += dt;
+            let time = fade.elapsed - fade.delay;
+
+            if fade.from.len() < buffer.len() {
+                fade.from = buffer.to_vec();
+            }
+
+            for parameter in layout.parameters() {
+                let channel = parameter.channel;
+                let to_controlled = target.is_some_and(|cue| cue.controls(channel));
+                let from_controlled = fade.from_mask.get(channel).copied().unwrap_or(false);
+
+                if !to_controlled && !from_controlled {
+                    continue;
+                }
+
+                let from = parameter.read(&fade.from);
+                let to = match target {
+                    Some(cue) if to_controlled => parameter.read(&cue.buffer),
+                    _ => parameter.read(buffer),
+                };
+
+                let fade_time = if layout.kind(channel) == ChannelKind::Control {
+                    0.0
+                } else if to >= from {
+                    fade.fade_in
+                } else {
+                    fade.fade_out
+                };
+
+                let progress = if fade_time > 0.0 {
+                    (time / fade_time).clamp(0.0, 1.0)
+                } else if time >= 0.0 {
+                    1.0
+                } else {
+                    0.0
+                };
+
+                parameter.write(buffer, from + (to - from) * progress);
+
+                for channel in parameter.channels() {
+                    self.controlled[channel] = true;
+                }
+            }
+
+            if time >= fade.fade_in.max(fade.fade_out) {
+                self.fade = None;
+            }
+        } else if let Some(cue) = target {
+            for parameter in layout.parameters() {
+                if cue.controls(parameter.channel) {
+                    for channel in parameter.channels() {
+                        buffer[channel] = cue.buffer[channel];
+                        self.controlled[channel] = true;
+                    }
+                }
+            }
+        }
+
+        self.output.clear();
+        self.output.extend_from_slice(buffer);
+    }
+}
+
+#[cfg(feature = "ui")]
+impl CuePlayer {
+    /// `selected` masks the channels of the selected devices.
+    /// GO, BACK and Release are returned as commands so they can be sent to the server.
+    pub fn ui(&mut self, ui: &mut Ui, cues: &mut Vec<Cue>, selected: &[bool]) -> Option<Command> {
+        let mut command = None;
+
+        CollapsingHeader::new("Cues")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("GO").clicked() {
+                        command = Some(Command::Go);
+                    }
+
+                    if ui.button("BACK").clicked() {
+                        command = Some(Command::Back);
+                    }
+
+                    if ui.button("Release").clicked() {
+                        command = Some(Command::Release);
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Record").clicked() {
+                        let mask = vec![true; self.output.len()];
+                        let name = format!("Cue {}", cues.len() + 1);
+                        cues.push(Cue::record(name, &self.output, mask));
+                    }
+
+                    if ui
+                        .add_enabled(selected.contains(&true), Button::new("Record Selected"))
+                        .clicked()
+                    {
+                        let name = format!("Cue {}", cues.len() + 1);
+                        cues.push(Cue::record(name, &self.output, selected.to_vec()));
+                    }
+
+                    if self.fade.is_some() {
+                        ui.label("Fading");
+                    }
+                });
+
+                let mut remove = None;
+
+                for (index, cue) in cues.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(if self.current == Some(index) {
+                            "▶"
+                        } else {
+                            "  "
+                        });
+                        ui.text_edit_singleline(&mut cue.name);
+
+                        ui.label("Fade in");
+                        DragValue::new(&mut cue.fade_in)
+                            .clamp_range(0.0..=60.0)
+                            .speed(0.1)
+                            .suffix(" s")
+                            .ui(ui);
+                        ui.label("Fade out");
+                        DragValue::new(&mut cue.fade_out)
+                            .clamp_range(0.0..=60.0)
+                            .speed(0.1)
+                            .suffix(" s")
+                            .ui(ui);
+                        ui.label("Delay");
+                        DragValue::new(&mut cue.delay)
+                            .clamp_range(0.0..=60.0)
+                            .speed(0.1)
+                            .suffix(" s")
+                            .ui(ui);
+
+                        if ui.button("Update").clicked() {
+                            cue.buffer = self.output.clone();
+                        }
+
+                        if ui.button("Delete").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                }
+
+                if let Some(index) = remove {
+                    cues.remove(index);
+
+                    match self.current {
+                        Some(current) if current == index => self.current = None,
+                        Some(current) if current > index => self.current = Some(current - 1),
+                        _ => (),
+                    }
+                }
+            });
+
+        command
+    }
+}
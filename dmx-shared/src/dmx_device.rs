@@ -0,0 +1,792 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+use crate::{
+    channel::ChannelWidget,
+    effect::Effect,
+    response::Response,
+    trigger::{Trigger, TriggerAction},
+    DmxColor, DmxMessage,
+};
+
+#[cfg(feature = "ui")]
+use crate::{
+    clock::{format_beats, BEAT_DIVISIONS},
+    playback::{Command, TriggerTarget, UNIVERSE},
+};
+#[cfg(feature = "ui")]
+use egui::{self, Checkbox, CollapsingHeader, ComboBox, DragValue, Ui, Widget};
+
+/// What drives the value of a channel.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+enum Source {
+    /// The value is set by hand.
+    Manual,
+    #[default]
+    Curve,
+    Effect,
+    /// Momentary or timed output, e.g. a smoke machine.
+    Trigger,
+}
+
+#[cfg(feature = "ui")]
+impl Source {
+    const ALL: [Source; 4] = [
+        Source::Manual,
+        Source::Curve,
+        Source::Effect,
+        Source::Trigger,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Source::Manual => "Manual",
+            Source::Curve => "Curve",
+            Source::Effect => "Effect",
+            Source::Trigger => "Trigger",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Timeline {
+    id: i8,
+    red: ChannelWidget,
+    green: ChannelWidget,
+    blue: ChannelWidget,
+    color: DmxColor,
+    gain: f32,
+    offset: f32,
+    #[serde(default)]
+    source: Source,
+    #[serde(default)]
+    effect: Effect,
+    #[serde(default)]
+    trigger: Trigger,
+}
+
+impl Timeline {
+    pub(crate) fn new(id: i8) -> Self {
+        Self {
+            id,
+            red: ChannelWidget::new(),
+            green: ChannelWidget::new(),
+            blue: ChannelWidget::new(),
+            color: DmxColor::default(),
+            gain: 1.0,
+            offset: 0.0,
+            source: Source::Manual,
+            effect: Effect::default(),
+            trigger: Trigger::default(),
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    pub(crate) fn ui(&mut self, ui: &mut Ui, time: f32) {
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source(ui.id().with("source"))
+                .selected_text(self.source.name())
+                .show_ui(ui, |ui| {
+                    for source in Source::ALL {
+                        ui.selectable_value(&mut self.source, source, source.name());
+                    }
+                });
+            ui.label("Gain");
+            DragValue::new(&mut self.gain)
+                .clamp_range(0.0..=1.0)
+                .speed(0.01)
+                .ui(ui);
+            ui.label("Offset");
+            DragValue::new(&mut self.offset)
+                .clamp_range(0.0..=1000.0)
+                .speed(1.0)
+                .ui(ui);
+        });
+
+        match self.source {
+            Source::Manual => (),
+            Source::Curve => {
+                self.red.ui(ui, time + self.offset);
+            }
+            Source::Effect => {
+                self.effect.ui(ui, ui.id().with("effect"));
+            }
+            Source::Trigger => {
+                self.trigger.ui(ui);
+            }
+        }
+    }
+
+    /// Advances the trigger by `dt` seconds, triggers run in real time even when paused.
+    pub(crate) fn update_trigger(&mut self, dt: f32) {
+        if self.source == Source::Trigger {
+            self.trigger.update(dt);
+        }
+    }
+
+    /// Whether the level changes over time, curves and effects only move while `playing`.
+    pub(crate) fn is_animated(&self, playing: bool) -> bool {
+        match self.source {
+            Source::Manual => false,
+            Source::Curve | Source::Effect => playing,
+            Source::Trigger => self.trigger.is_active(),
+        }
+    }
+
+    pub(crate) fn trigger(&mut self, action: TriggerAction) {
+        self.trigger.apply(action);
+    }
+
+    /// Keeps the trigger state of `previous` when the show is replaced.
+    pub(crate) fn carry_over(&mut self, previous: &Timeline) {
+        self.trigger.carry_over(&previous.trigger);
+    }
+
+    /// Hold and fire buttons if the timeline is a trigger.
+    #[cfg(feature = "ui")]
+    pub(crate) fn trigger_ui(&self, ui: &mut Ui, label: String) -> Option<TriggerAction> {
+        if self.source != Source::Trigger {
+            return None;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(label);
+            self.trigger.buttons_ui(ui)
+        })
+        .inner
+    }
+
+    /// Returns the level in 0..1 at `time`, `None` for manual channels.
+    pub(crate) fn level(&self, time: f32, fixture: usize, fixtures: usize) -> Option<f32> {
+        let time = time + self.offset;
+
+        match self.source {
+            Source::Manual => None,
+            Source::Curve => Some(self.red.value(time)),
+            Source::Effect => Some(self.effect.value(time, fixture, fixtures)),
+            Source::Trigger => Some(self.trigger.level()),
+        }
+        .map(|level| level * self.gain)
+    }
+}
+
+/// What a channel controls on the fixture, decides how masters and fades treat it.
+/// RGB fixtures without a dimmer channel should mark their color channels as intensity.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum ChannelKind {
+    /// Scaled by masters and blackout.
+    #[default]
+    Intensity,
+    Color,
+    Position,
+    /// Gobos, macros and modes. Jumps to the new value instead of fading.
+    Control,
+}
+
+#[cfg(feature = "ui")]
+impl ChannelKind {
+    const ALL: [ChannelKind; 4] = [
+        ChannelKind::Intensity,
+        ChannelKind::Color,
+        ChannelKind::Position,
+        ChannelKind::Control,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ChannelKind::Intensity => "Intensity",
+            ChannelKind::Color => "Color",
+            ChannelKind::Position => "Position",
+            ChannelKind::Control => "Control",
+        }
+    }
+}
+
+/// A value that occupies one channel, or two for 16-bit values
+/// where the first channel holds the coarse byte and the next the fine byte.
+#[derive(Clone, Copy)]
+pub struct Parameter {
+    pub channel: usize,
+    pub wide: bool,
+}
+
+impl Parameter {
+    pub fn channels(self) -> Range<usize> {
+        self.channel..self.channel + if self.wide { 2 } else { 1 }
+    }
+
+    pub fn max(self) -> u16 {
+        if self.wide {
+            u16::MAX
+        } else {
+            u8::MAX as u16
+        }
+    }
+
+    pub fn get(self, buffer: &[u8]) -> u16 {
+        if self.wide {
+            u16::from_be_bytes([buffer[self.channel], buffer[self.channel + 1]])
+        } else {
+            buffer[self.channel] as u16
+        }
+    }
+
+    pub fn set(self, buffer: &mut [u8], value: u16) {
+        if self.wide {
+            buffer[self.channel..self.channel + 2].copy_from_slice(&value.to_be_bytes());
+        } else {
+            buffer[self.channel] = value.min(u8::MAX as u16) as u8;
+        }
+    }
+
+    /// Returns the value in 0..1 at full resolution.
+    pub fn read(self, buffer: &[u8]) -> f32 {
+        self.get(buffer) as f32 / self.max() as f32
+    }
+
+    /// Writes a value in 0..1 at full resolution.
+    pub fn write(self, buffer: &mut [u8], value: f32) {
+        self.set(
+            buffer,
+            (value.clamp(0.0, 1.0) * self.max() as f32).round() as u16,
+        );
+    }
+}
+
+/// How the channels of a universe are patched. Unpatched channels count as 8-bit intensity.
+pub struct Layout {
+    kinds: Vec<ChannelKind>,
+    parameters: Vec<Parameter>,
+}
+
+impl Layout {
+    pub fn new(devices: &[DmxDevice], len: usize) -> Self {
+        let mut kinds = vec![ChannelKind::Intensity; len];
+        let mut wide = vec![false; len];
+
+        for device in devices {
+            let start = device.adress as usize;
+
+            for parameter in device.parameters() {
+                let kind = device.kind(parameter.channel);
+                let channels = parameter.channels();
+                let channels = start + channels.start..start + channels.end;
+
+                if channels.end > len {
+                    continue;
+                }
+
+                for channel in channels.clone() {
+                    kinds[channel] = kind;
+                    wide[channel] = false;
+                }
+                wide[channels.start] = parameter.wide;
+            }
+        }
+
+        let mut parameters = Vec::new();
+        let mut channel = 0;
+
+        while channel < len {
+            let parameter = Parameter {
+                channel,
+                wide: wide[channel] && channel + 1 < len,
+            };
+            channel = parameter.channels().end;
+            parameters.push(parameter);
+        }
+
+        Self { kinds, parameters }
+    }
+
+    pub fn kind(&self, channel: usize) -> ChannelKind {
+        self.kinds.get(channel).copied().unwrap_or_default()
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+}
+
+/// Writes the timeline levels of `parameters` into `values`.
+fn evaluate(
+    values: &mut [u8],
+    parameters: &[Parameter],
+    timelines: &[Timeline],
+    time: f32,
+    fixture: usize,
+    fixtures: usize,
+) {
+    for parameter in parameters {
+        if let Some(level) = timelines
+            .get(parameter.channel)
+            .and_then(|timeline| timeline.level(time, fixture, fixtures))
+        {
+            parameter.write(values, level);
+        }
+    }
+}
+
+/// Where a device is in its cycle, see `DmxDevice::advance`.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+pub struct Position {
+    /// Number of cycles played.
+    pub phase: f64,
+    /// Offset from the master clock after seeking a synced device.
+    pub seek_offset: f64,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum LoopMode {
+    #[default]
+    Loop,
+    PingPong,
+    /// Play the cycle once and hold the last position.
+    OneShot,
+}
+
+impl LoopMode {
+    #[cfg(feature = "ui")]
+    const ALL: [LoopMode; 3] = [LoopMode::Loop, LoopMode::PingPong, LoopMode::OneShot];
+
+    #[cfg(feature = "ui")]
+    fn name(self) -> &'static str {
+        match self {
+            LoopMode::Loop => "Loop",
+            LoopMode::PingPong => "Ping-pong",
+            LoopMode::OneShot => "One-shot",
+        }
+    }
+
+    /// Maps a phase counted in cycles to a timeline position, 0..1000.
+    fn time(self, phase: f64) -> f32 {
+        let position = match self {
+            LoopMode::Loop => phase.rem_euclid(1.0),
+            LoopMode::PingPong => {
+                let phase = phase.rem_euclid(2.0);
+                if phase < 1.0 {
+                    phase
+                } else {
+                    2.0 - phase
+                }
+            }
+            LoopMode::OneShot => phase.clamp(0.0, 1.0),
+        };
+
+        (position * 1000.0) as f32
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct DmxDevice {
+    pub enabled: bool,
+    #[serde(skip)]
+    pub selected: bool,
+    adress: u16,
+    size: u16,
+    name: String,
+    cycle_length: f32,
+    /// Cycle length in beats of the master clock, `None` runs freely using `cycle_length` seconds.
+    #[serde(default)]
+    beats: Option<f32>,
+    #[serde(default)]
+    loop_mode: LoopMode,
+    timelines: Vec<Timeline>,
+    #[serde(default)]
+    kinds: Vec<ChannelKind>,
+    /// Channels that hold the coarse byte of a 16-bit value, the next channel holds the fine byte.
+    #[serde(default)]
+    wide: Vec<bool>,
+    #[serde(default)]
+    responses: Vec<Response>,
+
+    /// Runtime state, rebuilt by `rehydrate` after loading.
+    #[serde(skip)]
+    values: Vec<u8>,
+    #[serde(skip)]
+    time: f32,
+    /// Number of cycles played, the loop mode maps it to `time`.
+    #[serde(skip)]
+    phase: f64,
+    /// Offset from the master clock after seeking a synced device.
+    #[serde(skip)]
+    seek_offset: f64,
+    //lights: [i32; 5],
+}
+
+impl DmxDevice {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    fn kind(&self, channel: usize) -> ChannelKind {
+        self.kinds.get(channel).copied().unwrap_or_default()
+    }
+
+    /// Parameters of the device, channels are relative to its address.
+    fn parameters(&self) -> Vec<Parameter> {
+        let size = self.values.len();
+        let mut parameters = Vec::new();
+        let mut channel = 0;
+
+        while channel < size {
+            let parameter = Parameter {
+                channel,
+                wide: self.wide.get(channel).copied().unwrap_or(false) && channel + 1 < size,
+            };
+            channel = parameter.channels().end;
+            parameters.push(parameter);
+        }
+
+        parameters
+    }
+
+    /// Universe channels patched to this device.
+    pub fn channels(&self) -> Range<usize> {
+        let start = self.adress as usize;
+        start..start + self.size as usize
+    }
+
+    /// Rebuilds the runtime state that isn't saved so it matches `size`.
+    pub fn rehydrate(&mut self) {
+        let size = self.size as usize;
+
+        self.values.resize(size, 0);
+        self.timelines.resize(size, Timeline::new(0));
+        self.kinds.resize(size, ChannelKind::default());
+        self.wide.resize(size, false);
+        self.responses.resize(size, Response::default());
+        self.time = self.loop_mode.time(self.phase);
+    }
+
+    pub fn position(&self) -> Position {
+        Position {
+            phase: self.phase,
+            seek_offset: self.seek_offset,
+        }
+    }
+
+    pub fn set_position(&mut self, position: Position) {
+        self.phase = position.phase;
+        self.seek_offset = position.seek_offset;
+        self.time = self.loop_mode.time(self.phase);
+    }
+
+    pub fn resync(&mut self) {
+        self.phase = 0.0;
+        self.seek_offset = 0.0;
+        self.time = 0.0;
+    }
+
+    /// Moves the device to `position` (0..1000) within its cycle.
+    pub fn seek(&mut self, position: f32, clock_beats: f64) {
+        let phase = position as f64 / 1000.0;
+
+        if let Some(beats) = self.beats {
+            self.seek_offset = phase - clock_beats / beats as f64;
+        }
+
+        self.phase = phase;
+        self.time = self.loop_mode.time(phase);
+    }
+
+    /// Advances the cycle by `dt` seconds of show time, synced devices follow `clock_beats`.
+    pub fn advance(&mut self, dt: f32, clock_beats: f64) {
+        if let Some(beats) = self.beats {
+            self.phase = clock_beats / beats as f64 + self.seek_offset;
+        } else if self.cycle_length > 0.0 {
+            self.phase += (dt / self.cycle_length) as f64;
+        }
+
+        self.time = self.loop_mode.time(self.phase);
+    }
+
+    /// Renders the timelines for fixture `index` of `fixtures` into the device output.
+    pub fn evaluate(&mut self, index: usize, dmx_message: &mut DmxMessage, fixtures: usize) {
+        let parameters = self.parameters();
+        evaluate(
+            &mut self.values,
+            &parameters,
+            &self.timelines,
+            self.time,
+            index,
+            fixtures,
+        );
+
+        self.write(dmx_message);
+    }
+
+    /// Sets the value of a manual parameter, `channel` is its first channel.
+    pub fn set_level(&mut self, channel: usize, value: u16) {
+        if let Some(parameter) = self
+            .parameters()
+            .into_iter()
+            .find(|parameter| parameter.channel == channel)
+        {
+            parameter.set(&mut self.values, value);
+        }
+    }
+
+    pub fn trigger(&mut self, channel: usize, action: TriggerAction) {
+        if let Some(timeline) = self.timelines.get_mut(channel) {
+            timeline.trigger(action);
+        }
+    }
+
+    /// Keeps the cycle position, manual values, triggers and selection of `previous`,
+    /// so replacing the show with an edited copy doesn't interrupt the output.
+    pub fn carry_over(&mut self, previous: &DmxDevice) {
+        self.selected = previous.selected;
+        self.phase = previous.phase;
+        self.seek_offset = previous.seek_offset;
+        self.time = self.loop_mode.time(self.phase);
+
+        for (value, previous) in self.values.iter_mut().zip(&previous.values) {
+            *value = *previous;
+        }
+
+        for (timeline, previous) in self.timelines.iter_mut().zip(&previous.timelines) {
+            timeline.carry_over(previous);
+        }
+    }
+
+    /// Manual value changes are returned as a command so they can be sent to the server.
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ui: &mut Ui, index: usize) -> Option<Command> {
+        let device = index;
+        let mut command = None;
+
+        CollapsingHeader::new(format!("Device {index} ({})", self.name))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(if self.enabled { "Enabled" } else { "Disabled" });
+                    if ui.button("Toggle").clicked() {
+                        self.enabled = !self.enabled;
+                    }
+                    ui.checkbox(&mut self.selected, "Selected");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut self.name);
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Cycle");
+
+                    let mut synced = self.beats.is_some();
+                    if ui.checkbox(&mut synced, "Sync to clock").changed() {
+                        self.beats = synced.then_some(4.0);
+                    }
+
+                    if let Some(beats) = &mut self.beats {
+                        ComboBox::from_id_source(format!("Device {index} beats"))
+                            .selected_text(format_beats(*beats))
+                            .show_ui(ui, |ui| {
+                                for division in BEAT_DIVISIONS {
+                                    ui.selectable_value(beats, division, format_beats(division));
+                                }
+                            });
+                    } else {
+                        DragValue::new(&mut self.cycle_length).speed(0.01).ui(ui);
+                    }
+
+                    ComboBox::from_id_source(format!("Device {index} loop mode"))
+                        .selected_text(self.loop_mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in LoopMode::ALL {
+                                ui.selectable_value(&mut self.loop_mode, mode, mode.name());
+                            }
+                        });
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Address:");
+                    DragValue::new(&mut self.adress)
+                        .clamp_range(0..=(UNIVERSE as u16).saturating_sub(self.size))
+                        .speed(1.0)
+                        .ui(ui);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Size:");
+                    if DragValue::new(&mut self.size)
+                        .clamp_range(0..=(UNIVERSE as u16).saturating_sub(self.adress))
+                        .speed(1.0)
+                        .ui(ui)
+                        .changed()
+                    {
+                        self.rehydrate();
+                    }
+                });
+
+                let size = self.size as usize;
+
+                for parameter in self.parameters() {
+                    let index = parameter.channel;
+                    let timeline = &mut self.timelines[index];
+                    let kind = &mut self.kinds[index];
+
+                    ui.horizontal(|ui| {
+                        if parameter.wide {
+                            ui.label(format!("Channel {index}/{}", index + 1));
+                        } else {
+                            ui.label(format!("Channel {index}"));
+                        }
+                        ComboBox::from_id_source(ui.id().with(("kind", index)))
+                            .selected_text(kind.name())
+                            .show_ui(ui, |ui| {
+                                for k in ChannelKind::ALL {
+                                    ui.selectable_value(kind, k, k.name());
+                                }
+                            });
+                        ui.add_enabled(
+                            index + 1 < size,
+                            Checkbox::new(&mut self.wide[index], "16-bit"),
+                        );
+                        let mut temp_value = parameter.get(&self.values);
+                        if ui
+                            .add_enabled(
+                                timeline.source == Source::Manual,
+                                DragValue::new(&mut temp_value)
+                                    .clamp_range(0..=parameter.max())
+                                    .speed(if parameter.wide { 256.0 } else { 1.0 }),
+                            )
+                            .changed()
+                        {
+                            command = Some(Command::Level {
+                                device,
+                                channel: index,
+                                value: temp_value,
+                            });
+                        }
+                    });
+
+                    CollapsingHeader::new(format!("{index}: Timeline"))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            timeline.ui(ui, self.time);
+                            /*timeline.color.rgb[1] =
+                                (timeline.green.ui(ui, self.time + timeline.offset)
+                                    * timeline.gain
+                                    * 255.0) as u8;
+                            timeline.color.rgb[2] =
+                                (timeline.blue.ui(ui, self.time + timeline.offset)
+                                    * timeline.gain
+                                    * 255.0) as u8;
+                            if ui.button("delete track").clicked() {
+                                timeline.id = -1;
+                            }*/
+                            ui.add(egui::Separator::default());
+                        });
+
+                    CollapsingHeader::new(format!("{index}: Response"))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            self.responses[index].ui(ui, parameter.read(&self.values));
+                        });
+                }
+            });
+
+        command
+    }
+
+    pub fn is_animated(&self, playing: bool) -> bool {
+        self.enabled
+            && self
+                .timelines
+                .iter()
+                .any(|timeline| timeline.is_animated(playing))
+    }
+
+    pub fn update_triggers(&mut self, dt: f32) {
+        for timeline in &mut self.timelines {
+            timeline.update_trigger(dt);
+        }
+    }
+
+    /// Buttons for the trigger channels of the device.
+    #[cfg(feature = "ui")]
+    pub fn triggers_ui(&self, ui: &mut Ui, index: usize) -> Vec<Command> {
+        let mut commands = Vec::new();
+
+        for (channel, timeline) in self.timelines.iter().enumerate() {
+            if let Some(action) =
+                timeline.trigger_ui(ui, format!("Device {index} ({}) {channel}", self.name))
+            {
+                commands.push(Command::Trigger {
+                    target: TriggerTarget::Device(index),
+                    channel,
+                    action,
+                });
+            }
+        }
+
+        commands
+    }
+
+    /// Drives the device from the timelines of a group, overriding its own.
+    /// `member` is the position of the device in a group of `members`.
+    pub(crate) fn apply_group(
+        &mut self,
+        timelines: &[Timeline],
+        time_offset: f32,
+        member: usize,
+        members: usize,
+        dmx_message: &mut DmxMessage,
+    ) {
+        let parameters = self.parameters();
+        evaluate(
+            &mut self.values,
+            &parameters,
+            timelines,
+            self.time + time_offset,
+            member,
+            members,
+        );
+
+        self.write(dmx_message);
+    }
+
+    /// Applies the response of each channel to the device output in `buffer`.
+    pub fn apply_response(&self, buffer: &mut [u8]) {
+        if !self.enabled {
+            return;
+        }
+
+        let start = self.adress as usize;
+
+        for parameter in self.parameters() {
+            let Some(response) = self.responses.get(parameter.channel) else {
+                continue;
+            };
+
+            let parameter = Parameter {
+                channel: start + parameter.channel,
+                ..parameter
+            };
+
+            if parameter.channels().end <= buffer.len() {
+                parameter.write(buffer, response.apply(parameter.read(buffer)));
+            }
+        }
+    }
+
+    fn write(&self, dmx_message: &mut DmxMessage) {
+        if self.enabled {
+            let start = self.adress as usize;
+
+            // Channels patched past the end of the universe are dropped.
+            for (slot, value) in dmx_message.buffer.iter_mut().skip(start).zip(&self.values) {
+                *slot = *value;
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+#[cfg(feature = "ui")]
+use egui::{ComboBox, DragValue, Id, Ui, Widget};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum EffectKind {
+    Sine,
+    Saw,
+    Square,
+    /// A pulse that travels across the fixtures.
+    Chase,
+    Strobe,
+    /// One component of a color wheel.
+    Rainbow,
+    /// Random level, changing `rate` times per cycle.
+    Flicker,
+}
+
+#[cfg(feature = "ui")]
+impl EffectKind {
+    const ALL: [EffectKind; 7] = [
+        EffectKind::Sine,
+        EffectKind::Saw,
+        EffectKind::Square,
+        EffectKind::Chase,
+        EffectKind::Strobe,
+        EffectKind::Rainbow,
+        EffectKind::Flicker,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            EffectKind::Sine => "Sine",
+            EffectKind::Saw => "Saw",
+            EffectKind::Square => "Square",
+            EffectKind::Chase => "Chase",
+            EffectKind::Strobe => "Strobe",
+            EffectKind::Rainbow => "Rainbow",
+            EffectKind::Flicker => "Flicker",
+        }
+    }
+}
+
+/// Procedural replacement for a hand drawn curve.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Effect {
+    pub kind: EffectKind,
+    /// Cycles per timeline cycle.
+    pub rate: f32,
+    /// Phase offset in cycles.
+    pub phase: f32,
+    /// Phase difference in cycles spread over all fixtures.
+    pub spread: f32,
+    pub amplitude: f32,
+    /// Fraction of the cycle that square and strobe are on.
+    pub width: f32,
+    /// Color wheel component used by rainbow, 0 red, 1 green, 2 blue.
+    pub component: u8,
+}
+
+impl Default for Effect {
+    fn default() -> Self {
+        Self {
+            kind: EffectKind::Sine,
+            rate: 1.0,
+            phase: 0.0,
+            spread: 0.0,
+            amplitude: 1.0,
+            width: 0.5,
+            component: 0,
+        }
+    }
+}
+
+impl Effect {
+    /// Evaluates the effect at `time` (0..1000) for fixture `fixture` of `fixtures`,
+    /// returns a value in 0..1.
+    pub fn value(&self, time: f32, fixture: usize, fixtures: usize) -> f32 {
+        let spread = if fixtures > 1 {
+            self.spread * fixture as f32 / fixtures as f32
+        } else {
+            0.0
+        };
+
+        let phase = time / 1000.0 * self.rate + self.phase - spread;
+        let x = phase.rem_euclid(1.0);
+
+        let value = match self.kind {
+            EffectKind::Sine => 0.5 - 0.5 * (TAU * x).cos(),
+            EffectKind::Saw => x,
+            EffectKind::Square | EffectKind::Strobe => {
+                if x < self.width {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            EffectKind::Chase => {
+                if x < 1.0 / fixtures.max(1) as f32 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            EffectKind::Rainbow => {
+                let h = x * 6.0;
+                match self.component {
+                    0 => (h - 3.0).abs() - 1.0,
+                    1 => 2.0 - (h - 2.0).abs(),
+                    _ => 2.0 - (h - 4.0).abs(),
+                }
+                .clamp(0.0, 1.0)
+            }
+            EffectKind::Flicker => noise(phase.floor() as i64, fixture),
+        };
+
+        value * self.amplitude
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Effect {
+    pub fn ui(&mut self, ui: &mut Ui, id: Id) {
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source(id.with("kind"))
+                .selected_text(self.kind.name())
+                .show_ui(ui, |ui| {
+                    for kind in EffectKind::ALL {
+                        if ui
+                            .selectable_value(&mut self.kind, kind, kind.name())
+                            .clicked()
+                        {
+                            match kind {
+                                EffectKind::Chase => self.spread = 1.0,
+                                EffectKind::Strobe => {
+                                    self.rate = self.rate.max(10.0);
+                                    self.width = 0.1;
+                                }
+                                EffectKind::Flicker => self.rate = self.rate.max(20.0),
+                                _ => (),
+                            }
+                        }
+                    }
+                });
+
+            ui.label("Rate");
+            DragValue::new(&mut self.rate)
+                .clamp_range(0.0..=100.0)
+                .speed(0.1)
+                .ui(ui);
+            ui.label("Phase");
+            DragValue::new(&mut self.phase)
+                .clamp_range(0.0..=1.0)
+                .speed(0.01)
+                .ui(ui);
+            ui.label("Spread");
+            DragValue::new(&mut self.spread)
+                .clamp_range(-4.0..=4.0)
+                .speed(0.01)
+                .ui(ui);
+            ui.label("Amplitude");
+            DragValue::new(&mut self.amplitude)
+                .clamp_range(0.0..=1.0)
+                .speed(0.01)
+                .ui(ui);
+
+            match self.kind {
+                EffectKind::Square | EffectKind::Strobe => {
+                    ui.label("Width");
+                    DragValue::new(&mut self.width)
+                        .clamp_range(0.0..=1.0)
+                        .speed(0.01)
+                        .ui(ui);
+                }
+                EffectKind::Rainbow => {
+                    ui.label("Component");
+                    ui.selectable_value(&mut self.component, 0, "Red");
+                    ui.selectable_value(&mut self.component, 1, "Green");
+                    ui.selectable_value(&mut self.component, 2, "Blue");
+                }
+                _ => (),
+            }
+        });
+    }
+}
+
+/// Deterministic noise in 0..1 for a step and fixture.
+pub fn noise(step: i64, fixture: usize) -> f32 {
+    let mut x = (step as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (fixture as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    x ^= x >> 31;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 29;
+
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dmx_device::{DmxDevice, Timeline},
+    effect::noise,
+    trigger::TriggerAction,
+    DmxMessage,
+};
+
+#[cfg(feature = "ui")]
+use crate::playback::{Command, TriggerTarget};
+#[cfg(feature = "ui")]
+use egui::{CollapsingHeader, ComboBox, DragValue, Ui, Widget};
+
+/// How the phase offset is distributed over the members of a group.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum SpreadMode {
+    #[default]
+    Linear,
+    Reverse,
+    CenterOut,
+    Random,
+}
+
+impl SpreadMode {
+    #[cfg(feature = "ui")]
+    const ALL: [SpreadMode; 4] = [
+        SpreadMode::Linear,
+        SpreadMode::Reverse,
+        SpreadMode::CenterOut,
+        SpreadMode::Random,
+    ];
+
+    #[cfg(feature = "ui")]
+    fn name(self) -> &'static str {
+        match self {
+            SpreadMode::Linear => "Linear",
+            SpreadMode::Reverse => "Reverse",
+            SpreadMode::CenterOut => "Center out",
+            SpreadMode::Random => "Random",
+        }
+    }
+
+    /// Fraction of the spread, 0..1, applied to member `member` of `members`.
+    fn fraction(self, member: usize, members: usize) -> f32 {
+        if members < 2 {
+            return 0.0;
+        }
+
+        match self {
+            SpreadMode::Linear => member as f32 / members as f32,
+            SpreadMode::Reverse => (members - 1 - member) as f32 / members as f32,
+            SpreadMode::CenterOut => {
+                (2.0 * member as f32 - (members - 1) as f32).abs() / (2 * members) as f32
+            }
+            SpreadMode::Random => noise(member as i64, members),
+        }
+    }
+}
+
+/// A set of devices driven by one set of timelines.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FixtureGroup {
+    pub name: String,
+    /// Indices into the device list, in spread order.
+    pub members: Vec<usize>,
+    pub spread_mode: SpreadMode,
+    /// Phase offset in cycles spread over the members.
+    pub spread: f32,
+    timelines: Vec<Timeline>,
+}
+
+impl FixtureGroup {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            members: Vec::new(),
+            spread_mode: SpreadMode::Linear,
+            spread: 0.0,
+            timelines: Vec::new(),
+        }
+    }
+
+    pub fn apply(&self, devices: &mut [DmxDevice], dmx_message: &mut DmxMessage) {
+        let members = self.members.len();
+
+        for (member, index) in self.members.iter().enumerate() {
+            if let Some(device) = devices.get_mut(*index) {
+                let offset = self.spread * self.spread_mode.fraction(member, members) * 1000.0;
+                device.apply_group(&self.timelines, offset, member, members, dmx_message);
+            }
+        }
+    }
+
+    pub fn is_animated(&self, playing: bool) -> bool {
+        !self.members.is_empty()
+            && self
+                .timelines
+                .iter()
+                .any(|timeline| timeline.is_animated(playing))
+    }
+
+    pub fn update_triggers(&mut self, dt: f32) {
+        for timeline in &mut self.timelines {
+            timeline.update_trigger(dt);
+        }
+    }
+
+    pub fn trigger(&mut self, channel: usize, action: TriggerAction) {
+        if let Some(timeline) = self.timelines.get_mut(channel) {
+            timeline.trigger(action);
+        }
+    }
+
+    /// Keeps the trigger state of `previous` when the show is replaced.
+    pub fn carry_over(&mut self, previous: &FixtureGroup) {
+        for (timeline, previous) in self.timelines.iter_mut().zip(&previous.timelines) {
+            timeline.carry_over(previous);
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl FixtureGroup {
+    /// Buttons for the trigger channels of the group.
+    pub fn triggers_ui(&self, ui: &mut Ui, index: usize) -> Vec<Command> {
+        let mut commands = Vec::new();
+
+        for (channel, timeline) in self.timelines.iter().enumerate() {
+            if let Some(action) =
+                timeline.trigger_ui(ui, format!("Group {index} ({}) {channel}", self.name))
+            {
+                commands.push(Command::Trigger {
+                    target: TriggerTarget::Group(index),
+                    channel,
+                    action,
+                });
+            }
+        }
+
+        commands
+    }
+
+    /// Returns true if the group should be deleted.
+    pub fn ui(&mut self, ui: &mut Ui, index: usize, devices: &[DmxDevice]) -> bool {
+        let mut delete = false;
+
+        CollapsingHeader::new(format!("Group {index} ({})", self.name))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut self.name);
+                    if ui.button("Delete Group").clicked() {
+                        delete = true;
+                    }
+                });
+
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Members:");
+                    for (device_index, device) in devices.iter().enumerate() {
+                        let mut member = self.members.contains(&device_index);
+                        if ui
+                            .checkbox(&mut member, format!("{device_index} ({})", device.name()))
+                            .changed()
+                        {
+                            if member {
+                                self.members.push(device_index);
+                            } else {
+                                self.members.retain(|i| *i != device_index);
+                            }
+                        }
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Spread");
+                    ComboBox::from_id_source(format!("Group {index} spread mode"))
+                        .selected_text(self.spread_mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in SpreadMode::ALL {
+                                ui.selectable_value(&mut self.spread_mode, mode, mode.name());
+                            }
+                        });
+                    DragValue::new(&mut self.spread)
+                        .clamp_range(0.0..=4.0)
+                        .speed(0.01)
+                        .ui(ui);
+                });
+
+                let size = self
+                    .members
+                    .iter()
+                    .filter_map(|index| devices.get(*index))
+                    .map(|device| device.size() as usize)
+                    .max()
+                    .unwrap_or(0);
+
+                if self.timelines.len() < size {
+                    self.timelines.resize(size, Timeline::new(0));
+                }
+
+                let time = self
+                    .members
+                    .first()
+                    .and_then(|index| devices.get(*index))
+                    .map_or(0.0, |device| device.time());
+
+                for (channel, timeline) in self.timelines.iter_mut().enumerate() {
+                    CollapsingHeader::new(format!("{channel}: Timeline"))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            timeline.ui(ui, time);
+                        });
+                }
+            });
+
+        delete
+    }
+}
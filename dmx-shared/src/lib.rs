@@ -1,10 +1,48 @@
+use playback::{Command, PlaybackState};
 use serde::{Deserialize, Serialize};
+use show::Show;
+
+pub mod channel;
+pub mod clock;
+pub mod cue;
+pub mod dmx_device;
+pub mod effect;
+pub mod group;
+pub mod master;
+pub mod playback;
+pub mod recording;
+pub mod response;
+pub mod show;
+pub mod trigger;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DmxMessage {
     pub buffer: Vec<u8>,
 }
 
+/// Messages sent by clients to the server. Older clients send a bare `DmxMessage`.
+#[derive(Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// A universe rendered by the client, output while the server has no show.
+    Frame(DmxMessage),
+    /// Replaces the show played by the server.
+    Show(Show),
+    /// Remote control of the playback on the server.
+    Command(Command),
+}
+
+/// Messages sent by the server to clients.
+#[derive(Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// The show played by the server, sent when a client connects and after every edit.
+    /// `None` until a client uploads one.
+    Show(Option<Show>),
+    /// A command sent by another client.
+    Command(Command),
+    /// The playback state of the server, sent when a client connects after the show.
+    Playback(PlaybackState),
+}
+
 #[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
 pub struct DmxColor {
     pub rgb: [u8; 3],
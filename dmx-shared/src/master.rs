@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+use crate::dmx_device::{ChannelKind, DmxDevice, Layout};
+
+#[cfg(feature = "ui")]
+use crate::{playback::Command, trigger::button_edge};
+#[cfg(feature = "ui")]
+use egui::{Button, CollapsingHeader, Slider, Ui, Widget};
+
+/// Intensity fader for a set of devices.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SubMaster {
+    pub name: String,
+    pub level: f32,
+    /// Indices into the device list.
+    pub devices: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Masters {
+    pub grand: f32,
+    pub subs: Vec<SubMaster>,
+}
+
+impl Default for Masters {
+    fn default() -> Self {
+        Self {
+            grand: 1.0,
+            subs: Vec::new(),
+        }
+    }
+}
+
+impl Masters {
+    /// Sets the grand master, or sub-master `sub`.
+    pub fn set_level(&mut self, sub: Option<usize>, level: f32) {
+        let level = level.clamp(0.0, 1.0);
+
+        match sub {
+            Some(sub) => {
+                if let Some(sub) = self.subs.get_mut(sub) {
+                    sub.level = level;
+                }
+            }
+            None => self.grand = level,
+        }
+    }
+
+    /// Scales the intensity parameters in `buffer`. The intensity of devices on a flashed
+    /// sub-master goes to full whatever the master levels, only blackout stops it.
+    pub fn apply(
+        &self,
+        devices: &[DmxDevice],
+        layout: &Layout,
+        buffer: &mut [u8],
+        blackout: bool,
+        flash: &[usize],
+    ) {
+        let grand = if blackout { 0.0 } else { self.grand };
+        let mut levels = vec![grand; buffer.len()];
+        let mut flashed = vec![false; buffer.len()];
+
+        for (index, device) in devices.iter().enumerate() {
+            let subs = || {
+                self.subs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, sub)| sub.devices.contains(&index))
+            };
+            let level = subs().map(|(_, sub)| sub.level).product::<f32>();
+            let flashing = !blackout && subs().any(|(sub_index, _)| flash.contains(&sub_index));
+
+            for channel in device.channels() {
+                if let Some(l) = levels.get_mut(channel) {
+                    *l *= level;
+                }
+                if let Some(f) = flashed.get_mut(channel) {
+                    *f |= flashing;
+                }
+            }
+        }
+
+        for parameter in layout.parameters() {
+            if layout.kind(parameter.channel) != ChannelKind::Intensity {
+                continue;
+            }
+
+            let level = levels[parameter.channel];
+
+            if flashed[parameter.channel] {
+                parameter.write(buffer, 1.0);
+            } else if level < 1.0 {
+                parameter.write(buffer, parameter.read(buffer) * level);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Masters {
+    /// `blackout` and `flashed` are the current playback state, changes to them and to
+    /// the master levels are returned as commands so they can be sent to the server.
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        devices: &[DmxDevice],
+        blackout: bool,
+        flashed: &[usize],
+    ) -> Vec<Command> {
+        let mut commands = Vec::new();
+
+        ui.horizontal(|ui| {
+            ui.label("Grand Master");
+            let mut grand = self.grand;
+            if Slider::new(&mut grand, 0.0..=1.0).ui(ui).changed() {
+                commands.push(Command::Master {
+                    sub: None,
+                    level: grand,
+                });
+            }
+
+            let mut toggled = blackout;
+            if ui.toggle_value(&mut toggled, "Blackout").changed() {
+                commands.push(Command::Blackout(toggled));
+            }
+        });
+
+        CollapsingHeader::new("Sub-masters")
+            .default_open(false)
+            .show(ui, |ui| {
+                if ui.button("Add Sub-master").clicked() {
+                    self.subs.push(SubMaster {
+                        name: format!("Sub {}", self.subs.len()),
+                        level: 1.0,
+                        devices: Vec::new(),
+                    });
+                }
+
+                let mut delete = None;
+
+                for (index, sub) in self.subs.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut sub.name);
+
+                        let mut level = sub.level;
+                        if Slider::new(&mut level, 0.0..=1.0).ui(ui).changed() {
+                            commands.push(Command::Master {
+                                sub: Some(index),
+                                level,
+                            });
+                        }
+
+                        let flash = ui.add(Button::new("Flash").selected(flashed.contains(&index)));
+                        if let Some(down) = button_edge(ui, &flash) {
+                            commands.push(Command::Flash { sub: index, down });
+                        }
+
+                        if ui.button("Delete").clicked() {
+                            delete = Some(index);
+                        }
+                    });
+
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label("Devices:");
+                        for (device_index, device) in devices.iter().enumerate() {
+                            let mut assigned = sub.devices.contains(&device_index);
+                            if ui
+                                .checkbox(
+                                    &mut assigned,
+                                    format!("{device_index} ({})", device.name()),
+                                )
+                                .changed()
+                            {
+                                if assigned {
+                                    sub.devices.push(device_index);
+                                } else {
+                                    sub.devices.retain(|i| *i != device_index);
+                                }
+                            }
+                        }
+                    });
+
+                    ui.separator();
+                }
+
+                if let Some(index) = delete {
+                    self.subs.remove(index);
+                }
+            });
+
+        commands
+    }
+}
@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::Clock,
+    cue::CuePlayer,
+    dmx_device::{Layout, Position},
+    show::Show,
+    trigger::TriggerAction,
+    DmxMessage,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Number of channels in a rendered universe.
+pub const UNIVERSE: usize = 512;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TriggerTarget {
+    /// Index into the device list.
+    Device(usize),
+    /// Index into the group list.
+    Group(usize),
+}
+
+/// Live control of the playback. Clients apply them locally and send them to the
+/// server when it plays the show, edits to the show itself are sent as a whole.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Command {
+    Play,
+    Pause,
+    /// Pause and move the clock and all devices back to the start.
+    Stop,
+    /// Move all devices to a position in their cycle, 0..1000.
+    Seek(f32),
+    /// Move the clock and all devices back to the start without pausing.
+    Resync,
+    /// Tap tempo, timed where the command is applied.
+    Tap,
+    Go,
+    Back,
+    Release,
+    Blackout(bool),
+    /// Flash button of sub-master `sub` pressed or let go.
+    Flash {
+        sub: usize,
+        down: bool,
+    },
+    /// Value of a manual parameter, `channel` is its first channel within the device.
+    Level {
+        device: usize,
+        channel: usize,
+        value: u16,
+    },
+    Trigger {
+        target: TriggerTarget,
+        channel: usize,
+        action: TriggerAction,
+    },
+    /// Level of the grand master, or of sub-master `sub`. Sent while the fader moves,
+    /// the level is saved with the show.
+    Master {
+        sub: Option<usize>,
+        level: f32,
+    },
+}
+
+impl Command {
+    /// Applies the command to `show` if it edits the show itself rather than the
+    /// playback, returns whether it did.
+    pub fn edit(&self, show: &mut Show) -> bool {
+        match *self {
+            Command::Master { sub, level } => {
+                show.masters.set_level(sub, level);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Playback state sent to clients that connect to the server, so they start in step
+/// with it. The relayed commands keep them in step from then on.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlaybackState {
+    pub playing: bool,
+    pub blackout: bool,
+    pub flash: Vec<usize>,
+    /// Position of the master clock.
+    pub beats: f64,
+    pub cue: Option<usize>,
+    /// Of every device in the show.
+    pub positions: Vec<Position>,
+}
+
+/// Playback state that isn't part of the show. Renders the universe of a show,
+/// in the client for preview and output, and headless in the server.
+pub struct Playback {
+    pub clock: Clock,
+    pub playing: bool,
+    pub cue_player: CuePlayer,
+    pub blackout: bool,
+    pub flash: Vec<usize>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            clock: Clock::default(),
+            playing: true,
+            cue_player: CuePlayer::default(),
+            blackout: false,
+            flash: Vec::new(),
+        }
+    }
+}
+
+impl Playback {
+    pub fn apply(&mut self, show: &mut Show, command: Command) {
+        if command.edit(show) {
+            return;
+        }
+
+        match command {
+            Command::Play => self.playing = true,
+            Command::Pause => self.playing = false,
+            Command::Stop => {
+                self.playing = false;
+                self.resync(show);
+            }
+            Command::Seek(position) => {
+                for device in &mut show.devices {
+                    device.seek(position, self.clock.beats);
+                }
+            }
+            Command::Resync => self.resync(show),
+            Command::Tap => {
+                if let Some(tapped) = self.clock.tap(Instant::now()) {
+                    show.bpm = tapped.clamp(20.0, 300.0);
+                }
+            }
+            Command::Go => self.cue_player.go(&show.cues),
+            Command::Back => self.cue_player.back(&show.cues),
+            Command::Release => self.cue_player.release(&show.cues),
+            Command::Blackout(blackout) => self.blackout = blackout,
+            Command::Flash { sub, down } => {
+                self.flash.retain(|flashed| *flashed != sub);
+                if down {
+                    self.flash.push(sub);
+                }
+            }
+            Command::Level {
+                device,
+                channel,
+                value,
+            } => {
+                if let Some(device) = show.devices.get_mut(device) {
+                    device.set_level(channel, value);
+                }
+            }
+            Command::Trigger {
+                target,
+                channel,
+                action,
+            } => match target {
+                TriggerTarget::Device(index) => {
+                    if let Some(device) = show.devices.get_mut(index) {
+                        device.trigger(channel, action);
+                    }
+                }
+                TriggerTarget::Group(index) => {
+                    if let Some(group) = show.groups.get_mut(index) {
+                        group.trigger(channel, action);
+                    }
+                }
+            },
+            Command::Master { .. } => (),
+        }
+    }
+
+    pub fn state(&self, show: &Show) -> PlaybackState {
+        PlaybackState {
+            playing: self.playing,
+            blackout: self.blackout,
+            flash: self.flash.clone(),
+            beats: self.clock.beats,
+            cue: self.cue_player.current(),
+            positions: show.devices.iter().map(|d| d.position()).collect(),
+        }
+    }
+
+    /// Takes over the playback state of another instance playing the same show.
+    pub fn restore(&mut self, show: &mut Show, state: PlaybackState) {
+        self.playing = state.playing;
+        self.blackout = state.blackout;
+        self.flash = state.flash;
+        self.clock.beats = state.beats;
+        self.cue_player.jump(state.cue);
+
+        for (device, position) in show.devices.iter_mut().zip(state.positions) {
+            device.set_position(position);
+        }
+    }
+
+    fn resync(&mut self, show: &mut Show) {
+        self.clock.resync();
+        for device in &mut show.devices {
+            device.resync();
+        }
+    }
+
+    /// Advances the show by `dt` seconds and renders the universe.
+    pub fn frame(&mut self, show: &mut Show, dt: f32) -> DmxMessage {
+        // Cues fade in real time, timelines only advance while playing.
+        let timeline_dt = if self.playing { dt } else { 0.0 };
+
+        self.clock.advance(show.bpm, timeline_dt);
+
+        let mut res = DmxMessage {
+            buffer: vec![0u8; UNIVERSE],
+        };
+
+        let fixtures = show.devices.len();
+        for (index, device) in show.devices.iter_mut().enumerate() {
+            device.advance(timeline_dt, self.clock.beats);
+            device.evaluate(index, &mut res, fixtures);
+        }
+
+        // Triggers use the frame time so they keep their timing while paused.
+        for device in &mut show.devices {
+            device.update_triggers(dt);
+        }
+        for group in &mut show.groups {
+            group.update_triggers(dt);
+        }
+
+        for group in &show.groups {
+            group.apply(&mut show.devices, &mut res);
+        }
+
+        let layout = Layout::new(&show.devices, res.buffer.len());
+
+        self.cue_player
+            .apply(&show.cues, &layout, &mut res.buffer, dt);
+
+        // Masters come last, so blackout and the grand master aren't bent by the curves.
+        for device in &show.devices {
+            device.apply_response(&mut res.buffer);
+        }
+
+        show.masters.apply(
+            &show.devices,
+            &layout,
+            &mut res.buffer,
+            self.blackout,
+            &self.flash,
+        );
+
+        res
+    }
+
+    /// Whether the output changes over time without any input.
+    pub fn is_animated(&self, show: &Show) -> bool {
+        self.cue_player.is_fading()
+            || show.devices.iter().any(|d| d.is_animated(self.playing))
+            || show.groups.iter().any(|g| g.is_animated(self.playing))
+    }
+}
@@ -0,0 +1,131 @@
+//! Binary format of universe recordings made by dmx-server.
+//!
+//! A recording starts with `MAGIC` and the format `VERSION` byte, followed by the frames in
+//! order. Each frame is its time since the start of the recording in milliseconds as a
+//! little endian `u32`, the length of the universe as a little endian `u16`, and the
+//! channel values. A frame holds until the next one, so unchanged frames aren't recorded.
+
+use std::{io, time::Duration};
+
+pub const MAGIC: &[u8; 4] = b"DMXR";
+
+/// Current recording format, bump it when the frame layout changes.
+pub const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Since the start of the recording.
+    pub time: Duration,
+    pub buffer: Vec<u8>,
+}
+
+/// Appends the header every recording starts with to `out`.
+pub fn encode_header(out: &mut Vec<u8>) {
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+}
+
+/// Appends `frame` to `out`, times past 49 days and universes past 65535 channels are cut.
+pub fn encode_frame(out: &mut Vec<u8>, frame: &Frame) {
+    let time = u32::try_from(frame.time.as_millis()).unwrap_or(u32::MAX);
+    let buffer = &frame.buffer[..frame.buffer.len().min(u16::MAX as usize)];
+
+    out.extend_from_slice(&time.to_le_bytes());
+    out.extend_from_slice(&(buffer.len() as u16).to_le_bytes());
+    out.extend_from_slice(buffer);
+}
+
+/// Reads the frames of a whole recording. A frame cut short at the end, as left by a
+/// recorder that was killed, is dropped.
+pub fn decode(data: &[u8]) -> io::Result<Vec<Frame>> {
+    let Some(rest) = data.strip_prefix(MAGIC) else {
+        return Err(invalid("not a DMX recording"));
+    };
+
+    let (&version, mut rest) = rest
+        .split_first()
+        .ok_or_else(|| invalid("not a DMX recording"))?;
+
+    if version != VERSION {
+        return Err(invalid(&format!(
+            "unsupported recording version {version}, expected {VERSION}"
+        )));
+    }
+
+    let mut frames = Vec::new();
+
+    while let [t0, t1, t2, t3, l0, l1, data @ ..] = rest {
+        let time = u32::from_le_bytes([*t0, *t1, *t2, *t3]);
+        let len = u16::from_le_bytes([*l0, *l1]) as usize;
+
+        let Some(buffer) = data.get(..len) else {
+            break;
+        };
+
+        frames.push(Frame {
+            time: Duration::from_millis(time.into()),
+            buffer: buffer.to_vec(),
+        });
+        rest = &data[len..];
+    }
+
+    Ok(frames)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(frames: &[Frame]) -> Vec<u8> {
+        let mut data = Vec::new();
+        encode_header(&mut data);
+        for frame in frames {
+            encode_frame(&mut data, frame);
+        }
+        data
+    }
+
+    fn frames() -> Vec<Frame> {
+        vec![
+            Frame {
+                time: Duration::ZERO,
+                buffer: vec![0; 128],
+            },
+            Frame {
+                time: Duration::from_millis(25),
+                buffer: (0..=255).collect(),
+            },
+            Frame {
+                time: Duration::from_millis(1500),
+                buffer: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(decode(&recording(&frames())).unwrap(), frames());
+    }
+
+    #[test]
+    fn drops_a_frame_cut_short() {
+        let mut data = recording(&frames()[..2]);
+        data.truncate(data.len() - 1);
+
+        assert_eq!(decode(&data).unwrap(), frames()[..1]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(decode(b"{\"version\": 1}").is_err());
+        assert!(decode(MAGIC).is_err());
+
+        let mut data = recording(&frames());
+        data[MAGIC.len()] = VERSION + 1;
+        assert!(decode(&data).is_err());
+    }
+}
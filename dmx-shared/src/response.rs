@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+use crate::channel::ChannelWidget;
+
+#[cfg(feature = "ui")]
+use egui::{ComboBox, DragValue, Ui, Widget};
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    SquareLaw,
+    SCurve,
+    /// User drawn lookup table.
+    Custom,
+}
+
+#[cfg(feature = "ui")]
+impl ResponseCurve {
+    const ALL: [ResponseCurve; 4] = [
+        ResponseCurve::Linear,
+        ResponseCurve::SquareLaw,
+        ResponseCurve::SCurve,
+        ResponseCurve::Custom,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ResponseCurve::Linear => "Linear",
+            ResponseCurve::SquareLaw => "Square law",
+            ResponseCurve::SCurve => "S-curve",
+            ResponseCurve::Custom => "Custom",
+        }
+    }
+}
+
+/// Output response of a channel, applied to the output before the masters dim it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Response {
+    pub curve: ResponseCurve,
+    /// Output limits in 0..1, the curve is scaled into this range.
+    pub min: f32,
+    pub max: f32,
+    pub invert: bool,
+    lut: ChannelWidget,
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self {
+            curve: ResponseCurve::Linear,
+            min: 0.0,
+            max: 1.0,
+            invert: false,
+            lut: ChannelWidget::lut(),
+        }
+    }
+}
+
+impl Response {
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+
+        let value = match self.curve {
+            ResponseCurve::Linear => value,
+            ResponseCurve::SquareLaw => value * value,
+            ResponseCurve::SCurve => value * value * (3.0 - 2.0 * value),
+            ResponseCurve::Custom => self.lut.value(value * 1000.0).clamp(0.0, 1.0),
+        };
+
+        let value = if self.invert { 1.0 - value } else { value };
+
+        self.min + value * (self.max - self.min)
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Response {
+    /// `input` is the current value before the response, shown on the custom curve.
+    pub fn ui(&mut self, ui: &mut Ui, input: f32) {
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source(ui.id().with("curve"))
+                .selected_text(self.curve.name())
+                .show_ui(ui, |ui| {
+                    for curve in ResponseCurve::ALL {
+                        ui.selectable_value(&mut self.curve, curve, curve.name());
+                    }
+                });
+            ui.label("Min");
+            DragValue::new(&mut self.min)
+                .clamp_range(0.0..=self.max)
+                .speed(0.01)
+                .ui(ui);
+            ui.label("Max");
+            DragValue::new(&mut self.max)
+                .clamp_range(self.min..=1.0)
+                .speed(0.01)
+                .ui(ui);
+            ui.checkbox(&mut self.invert, "Invert");
+        });
+
+        if self.curve == ResponseCurve::Custom {
+            self.lut.ui(ui, input * 1000.0);
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{fmt, io};
+
+use crate::{
+    clock, cue::Cue, dmx_device::DmxDevice, group::FixtureGroup, master::Masters,
+    playback::UNIVERSE,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// Current show file format, bump it and add a step to `migrate` when `Show` changes
+/// in a way `#[serde(default)]` can't handle.
+pub const VERSION: u32 = 1;
+
+pub const DEFAULT_PATH: &str = "state.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Show {
+    /// Show file format, see `migrate`.
+    #[serde(default)]
+    pub version: u32,
+    pub lights: [i32; 5],
+    pub devices: Vec<DmxDevice>,
+    #[serde(default = "clock::default_bpm")]
+    pub bpm: f32,
+    #[serde(default)]
+    pub cues: Vec<Cue>,
+    #[serde(default)]
+    pub groups: Vec<FixtureGroup>,
+    #[serde(default)]
+    pub masters: Masters,
+}
+
+impl Show {
+    /// Restores the runtime state of a freshly deserialized show.
+    pub fn rehydrate(&mut self) {
+        for device in &mut self.devices {
+            device.rehydrate();
+        }
+    }
+
+    /// Checks what serde can't, so a bad file or upload can't take down the playback.
+    pub fn validate(&self) -> Result<(), ShowError> {
+        for (index, device) in self.devices.iter().enumerate() {
+            if device.channels().end > UNIVERSE {
+                return Err(ShowError::Invalid(format!(
+                    "device {index} ({}) doesn't fit in the universe",
+                    device.name()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the playback state of `previous`, which this show is an edited copy of.
+    pub fn carry_over(&mut self, previous: &Show) {
+        for (device, previous) in self.devices.iter_mut().zip(&previous.devices) {
+            device.carry_over(previous);
+        }
+
+        for (group, previous) in self.groups.iter_mut().zip(&previous.groups) {
+            group.carry_over(previous);
+        }
+    }
+}
+
+impl Default for Show {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            lights: [0, 1, 2, 3, 4],
+            devices: Vec::new(),
+            bpm: clock::default_bpm(),
+            cues: Vec::new(),
+            groups: Vec::new(),
+            masters: Masters::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShowError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    /// The file was written by a newer version of the controller.
+    Version(u64),
+    Invalid(String),
+}
+
+impl fmt::Display for ShowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShowError::Io(e) => write!(f, "{e}"),
+            ShowError::Parse(e) => write!(f, "invalid show file: {e}"),
+            ShowError::Invalid(e) => write!(f, "invalid show: {e}"),
+            ShowError::Version(version) => write!(
+                f,
+                "show file version {version} is newer than the supported version {VERSION}"
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for ShowError {
+    fn from(e: io::Error) -> Self {
+        ShowError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ShowError {
+    fn from(e: serde_json::Error) -> Self {
+        ShowError::Parse(e)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(path: impl AsRef<Path>) -> Result<Show, ShowError> {
+    from_json(&std::fs::read_to_string(path)?)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(path: impl AsRef<Path>, show: &Show) -> Result<(), ShowError> {
+    std::fs::write(path, to_json(show)?)?;
+    Ok(())
+}
+
+pub fn from_json(s: &str) -> Result<Show, ShowError> {
+    let value = migrate(serde_json::from_str(s)?)?;
+    let mut show: Show = serde_json::from_value(value)?;
+    show.version = VERSION;
+    show.validate()?;
+    show.rehydrate();
+    Ok(show)
+}
+
+pub fn to_json(show: &Show) -> Result<String, ShowError> {
+    Ok(serde_json::to_string_pretty(show)?)
+}
+
+/// Upgrades a show file to the current version, one version at a time.
+fn migrate(mut value: Value) -> Result<Value, ShowError> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+
+    if version > VERSION as u64 {
+        return Err(ShowError::Version(version));
+    }
+
+    if version < 1 {
+        value = migrate_v0(value);
+    }
+
+    Ok(value)
+}
+
+/// Files without a version. The first format kept a single `cycle_length` and the
+/// timelines on the show itself, move them into one device covering the lights. Later
+/// files added devices but still kept the timing and timelines on the show, those are
+/// copied into every device.
+fn migrate_v0(mut value: Value) -> Value {
+    if let Some(show) = value.as_object_mut() {
+        let timelines = show.remove("timelines").unwrap_or_else(|| json!([]));
+        let cycle_length = show.remove("cycle_length").unwrap_or_else(|| json!(5.0));
+        let size = timelines.as_array().map_or(0, Vec::len);
+
+        let devices = show
+            .entry("devices")
+            .or_insert_with(|| json!([{ "name": "Lights" }]));
+
+        if let Some(devices) = devices.as_array_mut() {
+            for device in devices.iter_mut().filter_map(Value::as_object_mut) {
+                device.entry("enabled").or_insert_with(|| json!(true));
+                device.entry("adress").or_insert_with(|| json!(0));
+                device.entry("size").or_insert_with(|| json!(size));
+                device.entry("name").or_insert_with(|| json!(""));
+                device
+                    .entry("cycle_length")
+                    .or_insert_with(|| cycle_length.clone());
+                device
+                    .entry("timelines")
+                    .or_insert_with(|| timelines.clone());
+            }
+        }
+
+        show.insert("version".to_owned(), json!(1));
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::Playback;
+
+    /// Written before show files had a version: devices only hold the patch, the timing
+    /// and timelines are still on the show.
+    const V0_SHOW: &str = r#"{
+        "cycle_length": 2.0,
+        "timelines": [
+            {
+                "id": 0,
+                "red": {
+                    "next_id": 2,
+                    "control_points": [[{"x": 0.0, "y": 100.0}, 0], [{"x": 1000.0, "y": 0.0}, 1]]
+                },
+                "green": {"next_id": 0, "control_points": []},
+                "blue": {"next_id": 0, "control_points": []},
+                "color": {"rgb": [0, 0, 0], "white": 0, "amber": 0, "uv": 0},
+                "gain": 1.0,
+                "offset": 0.0
+            },
+            {
+                "id": 1,
+                "red": {"next_id": 0, "control_points": []},
+                "green": {"next_id": 0, "control_points": []},
+                "blue": {"next_id": 0, "control_points": []},
+                "color": {"rgb": [0, 0, 0], "white": 0, "amber": 0, "uv": 0},
+                "gain": 1.0,
+                "offset": 0.0
+            }
+        ],
+        "lights": [0, 1, 2, 3, 4],
+        "devices": [{"enabled": true, "adress": 0, "size": 4, "name": "Par"}]
+    }"#;
+
+    fn v0_show() -> Value {
+        serde_json::from_str(V0_SHOW).unwrap()
+    }
+
+    #[test]
+    fn round_trip_rebuilds_runtime_state() {
+        let mut show = from_json(V0_SHOW).unwrap();
+        let json = to_json(&show).unwrap();
+        let mut loaded = from_json(&json).unwrap();
+
+        assert_eq!(to_json(&loaded).unwrap(), json);
+
+        let expected = Playback::default().frame(&mut show, 0.5);
+        let frame = Playback::default().frame(&mut loaded, 0.5);
+
+        assert_eq!(frame.buffer, expected.buffer);
+        assert!(frame.buffer.iter().any(|value| *value > 0));
+    }
+
+    #[test]
+    fn migrates_v0() {
+        let mut value = v0_show();
+        value.as_object_mut().unwrap().remove("devices");
+
+        let show = from_json(&value.to_string()).unwrap();
+
+        assert_eq!(show.version, VERSION);
+        assert_eq!(show.devices.len(), 1);
+        assert_eq!(show.devices[0].name(), "Lights");
+        assert_eq!(show.devices[0].size(), 2);
+    }
+
+    #[test]
+    fn migrates_v0_with_devices() {
+        let show = from_json(V0_SHOW).unwrap();
+
+        assert_eq!(show.version, VERSION);
+        assert_eq!(show.devices.len(), 1);
+        assert_eq!(show.devices[0].name(), "Par");
+        assert_eq!(show.devices[0].size(), 4);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let json = to_json(&Show {
+            version: VERSION + 1,
+            ..Show::default()
+        })
+        .unwrap();
+
+        assert!(matches!(
+            from_json(&json),
+            Err(ShowError::Version(version)) if version == VERSION as u64 + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_devices_outside_the_universe() {
+        let mut value = v0_show();
+        value["devices"][0]["adress"] = json!(65000);
+        value["devices"][0]["size"] = json!(1000);
+
+        assert!(matches!(
+            from_json(&value.to_string()),
+            Err(ShowError::Invalid(_))
+        ));
+    }
+}
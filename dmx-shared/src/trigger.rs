@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ui")]
+use egui::{Button, DragValue, Response, Ui, Widget};
+
+/// What an operator does with a trigger, sent to the server when it plays the show.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TriggerAction {
+    /// Start firing until released.
+    Press,
+    Release,
+    /// Fire for `duration` seconds.
+    Fire,
+}
+
+/// Momentary or timed output for effect devices like smoke machines.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Trigger {
+    /// Output level in 0..1 while firing.
+    pub level: f32,
+    /// Seconds to fire for when started with "Fire".
+    pub duration: f32,
+    /// Longest time in seconds the trigger may fire in one go, 0 disables the limit.
+    pub max_on: f32,
+    /// Seconds the trigger is locked out after reaching `max_on`.
+    /// The allowed on time also recovers at a rate of `max_on / cooldown` while off.
+    pub cooldown: f32,
+
+    #[serde(skip)]
+    held: bool,
+    #[serde(skip)]
+    timer: f32,
+    #[serde(skip)]
+    on_time: f32,
+    #[serde(skip)]
+    lockout: f32,
+    #[serde(skip)]
+    firing: bool,
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self {
+            level: 0.5,
+            duration: 3.0,
+            max_on: 10.0,
+            cooldown: 20.0,
+            held: false,
+            timer: 0.0,
+            on_time: 0.0,
+            lockout: 0.0,
+            firing: false,
+        }
+    }
+}
+
+impl Trigger {
+    pub fn level(&self) -> f32 {
+        if self.firing {
+            self.level
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether the trigger is held, firing or counting down and needs to be updated.
+    pub fn is_active(&self) -> bool {
+        self.held || self.firing || self.timer > 0.0 || self.lockout > 0.0 || self.on_time > 0.0
+    }
+
+    pub fn apply(&mut self, action: TriggerAction) {
+        match action {
+            TriggerAction::Press => self.held = true,
+            TriggerAction::Release => self.held = false,
+            TriggerAction::Fire => self.timer = self.duration,
+        }
+    }
+
+    /// Keeps the firing state and duty limit of `previous` when the show is replaced.
+    pub fn carry_over(&mut self, previous: &Trigger) {
+        self.held = previous.held;
+        self.timer = previous.timer;
+        self.on_time = previous.on_time;
+        self.lockout = previous.lockout;
+        self.firing = previous.firing;
+    }
+
+    /// Advances the trigger by `dt` seconds of real time, call once per frame.
+    pub fn update(&mut self, dt: f32) {
+        let requested = self.held || self.timer > 0.0;
+
+        self.timer = (self.timer - dt).max(0.0);
+
+        if self.lockout > 0.0 {
+            self.lockout = (self.lockout - dt).max(0.0);
+            self.timer = 0.0;
+            self.firing = false;
+            return;
+        }
+
+        if requested {
+            // The frame that reaches `max_on` still fires, the lockout starts after it.
+            if self.max_on > 0.0 && self.on_time >= self.max_on {
+                self.lockout = self.cooldown;
+                self.on_time = 0.0;
+                self.timer = 0.0;
+                self.firing = false;
+            } else {
+                self.on_time += dt;
+                self.firing = true;
+            }
+        } else {
+            self.firing = false;
+
+            self.on_time = if self.cooldown > 0.0 {
+                (self.on_time - dt * self.max_on / self.cooldown).max(0.0)
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Trigger {
+    /// Hold and fire buttons, returns what was pressed on this client.
+    pub fn buttons_ui(&self, ui: &mut Ui) -> Option<TriggerAction> {
+        let mut action = None;
+
+        let hold = ui.add(Button::new("Hold").selected(self.held));
+        if let Some(down) = button_edge(ui, &hold) {
+            action = Some(if down {
+                TriggerAction::Press
+            } else {
+                TriggerAction::Release
+            });
+        }
+
+        if ui.button(format!("Fire {} s", self.duration)).clicked() {
+            action = Some(TriggerAction::Fire);
+        }
+
+        if self.lockout > 0.0 {
+            ui.label(format!("Cooling down {:.0} s", self.lockout.ceil()));
+        } else if self.firing {
+            ui.label("Firing");
+        }
+
+        action
+    }
+
+    /// Settings, the buttons are in the triggers panel.
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Level");
+            DragValue::new(&mut self.level)
+                .clamp_range(0.0..=1.0)
+                .speed(0.01)
+                .ui(ui);
+            ui.label("Duration");
+            DragValue::new(&mut self.duration)
+                .clamp_range(0.0..=600.0)
+                .speed(0.1)
+                .suffix(" s")
+                .ui(ui);
+            ui.label("Max on");
+            DragValue::new(&mut self.max_on)
+                .clamp_range(0.0..=600.0)
+                .speed(0.1)
+                .suffix(" s")
+                .ui(ui);
+            ui.label("Cooldown");
+            DragValue::new(&mut self.cooldown)
+                .clamp_range(0.0..=600.0)
+                .speed(0.1)
+                .suffix(" s")
+                .ui(ui);
+        });
+    }
+}
+
+/// `Some(true)` when `button` was pressed on this client since the last frame and
+/// `Some(false)` when it was let go. Presses relayed from other clients only show up in
+/// the playback state, comparing the button with that would release them right away.
+#[cfg(feature = "ui")]
+pub(crate) fn button_edge(ui: &Ui, button: &Response) -> Option<bool> {
+    let down = button.is_pointer_button_down_on();
+    let was_down = ui
+        .data_mut(|data| std::mem::replace(data.get_temp_mut_or_default::<bool>(button.id), down));
+
+    (down != was_down).then_some(down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(max_on: f32, cooldown: f32) -> Trigger {
+        Trigger {
+            level: 1.0,
+            duration: 3.0,
+            max_on,
+            cooldown,
+            ..Trigger::default()
+        }
+    }
+
+    /// Whether the trigger fires in each of `seconds` one second updates.
+    fn run(trigger: &mut Trigger, seconds: usize) -> Vec<bool> {
+        (0..seconds)
+            .map(|_| {
+                trigger.update(1.0);
+                trigger.level() > 0.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fires_for_duration() {
+        let mut trigger = trigger(0.0, 0.0);
+        trigger.apply(TriggerAction::Fire);
+
+        assert_eq!(run(&mut trigger, 4), [true, true, true, false]);
+        assert!(!trigger.is_active());
+    }
+
+    #[test]
+    fn fires_while_held() {
+        let mut trigger = trigger(0.0, 0.0);
+        trigger.apply(TriggerAction::Press);
+        assert_eq!(run(&mut trigger, 5), [true; 5]);
+
+        trigger.apply(TriggerAction::Release);
+        assert_eq!(run(&mut trigger, 1), [false]);
+    }
+
+    #[test]
+    fn locks_out_after_max_on() {
+        let mut trigger = trigger(2.0, 3.0);
+        trigger.apply(TriggerAction::Press);
+
+        assert_eq!(
+            run(&mut trigger, 7),
+            [true, true, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn fire_is_ignored_during_lockout() {
+        let mut trigger = trigger(2.0, 3.0);
+        trigger.apply(TriggerAction::Fire);
+        assert_eq!(run(&mut trigger, 3), [true, true, false]);
+
+        trigger.apply(TriggerAction::Fire);
+        assert_eq!(run(&mut trigger, 2), [false, false]);
+    }
+
+    #[test]
+    fn on_time_recovers_while_off() {
+        let mut trigger = trigger(10.0, 20.0);
+        trigger.apply(TriggerAction::Press);
+        run(&mut trigger, 6);
+
+        // Recovers 2 of the 6 seconds used.
+        trigger.apply(TriggerAction::Release);
+        run(&mut trigger, 4);
+
+        trigger.apply(TriggerAction::Press);
+        assert_eq!(
+            run(&mut trigger, 7),
+            [true, true, true, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn carry_over_keeps_the_lockout() {
+        let mut previous = trigger(1.0, 3.0);
+        previous.apply(TriggerAction::Press);
+        run(&mut previous, 2);
+
+        let mut trigger = trigger(1.0, 3.0);
+        trigger.carry_over(&previous);
+        assert_eq!(run(&mut trigger, 3), [false, false, false]);
+    }
+}